@@ -1,3 +1,7 @@
+// DEPRECATED: superseded by the armandra-coordinator binary in src-tauri.
+// Kept for existing deployments only. It takes declines but doesn't resume
+// sessions, and result fields it doesn't know about are dropped from
+// aggregates.
 import express from 'express';
 import { createServer } from 'http';
 import { WebSocketServer } from 'ws';
@@ -22,7 +26,7 @@ wss.on('connection', (ws) => {
   console.log(`Client connected: ${clientId}`);
 
   // Add client to the clients map
-  clients.set(clientId, { ws, roomId: null });
+  clients.set(clientId, { ws, roomId: null, capacity: 1, worker: null, available: false });
 
   // Send the client their ID
   sendToClient(ws, 'connected', { clientId });
@@ -46,35 +50,65 @@ wss.on('connection', (ws) => {
 
 // Handle incoming messages
 function handleMessage(ws, clientId, data) {
-  const { type, payload } = data;
+  const receivedAt = Date.now();
+  const { type, payload, requestId } = data;
+
+  // Responses echo the request ID so the client can match them to the call
+  const respond = (responseType, responsePayload) =>
+    sendToClient(ws, responseType, responsePayload, requestId);
 
   switch (type) {
     case 'create-room':
-      createRoom(ws, clientId);
+      createRoom(respond, clientId, payload);
       break;
     case 'join-room':
-      joinRoom(ws, clientId, payload.roomId);
+      joinRoom(respond, clientId, payload);
       break;
     case 'configure-test':
-      configureTest(ws, clientId, payload.config);
+      configureTest(respond, clientId, payload.config);
       break;
     case 'start-test':
-      startTest(ws, clientId);
+      startTest(respond, clientId, payload.startDelayMs);
+      break;
+    case 'clock-sync':
+      respond('clock-sync-response', {
+        success: true,
+        clientSendTime: payload.clientSendTime,
+        serverReceiveTime: receivedAt,
+        serverSendTime: Date.now()
+      });
       break;
     case 'submit-results':
-      submitResults(ws, clientId, payload.results);
+      submitResults(respond, clientId, payload.results, payload.declined);
+      break;
+    case 'abort-test':
+      abortTest(respond, clientId, payload.reason);
+      break;
+    case 'metrics-delta':
+      recordMetricsDelta(clientId, payload.delta);
+      break;
+    case 'register-worker':
+      registerWorker(respond, clientId, payload);
+      break;
+    case 'assign-workers':
+      assignWorkers(respond, clientId, payload);
       break;
     case 'leave-room':
       leaveRoom(clientId);
-      sendToClient(ws, 'leave-room-response', { success: true });
+      clients.get(clientId).available = false;
+      respond('leave-room-response', { success: true });
+      break;
+    case 'resume-session':
+      // Clients fall back to rejoining their room when this fails
+      respond('resume-session-response', { success: false, error: 'This server does not resume sessions' });
       break;
     default:
-      sendToClient(ws, 'error', { error: 'Unknown message type' });
+      respond('error', { error: 'Unknown message type' });
   }
 }
 
 // Create a new room
-function createRoom(ws, clientId) {
+function createRoom(respond, clientId, { capacity, worker } = {}) {
   const roomId = uuidv4();
   rooms.set(roomId, {
     id: roomId,
@@ -87,15 +121,25 @@ function createRoom(ws, clientId) {
   // Update client's room
   const clientData = clients.get(clientId);
   clientData.roomId = roomId;
+  clientData.capacity = parseCapacity(capacity);
+  clientData.worker = worker || null;
+  clientData.available = false;
 
   console.log(`Room created: ${roomId} by ${clientId}`);
-  sendToClient(ws, 'create-room-response', { success: true, roomId });
+  respond('create-room-response', {
+    success: true,
+    roomId,
+    config: null,
+    status: 'waiting',
+    clientCount: 1,
+    members: roomMembers(rooms.get(roomId))
+  });
 }
 
 // Join an existing room
-function joinRoom(ws, clientId, roomId) {
+function joinRoom(respond, clientId, { roomId, capacity, worker } = {}) {
   if (!rooms.has(roomId)) {
-    sendToClient(ws, 'join-room-response', { success: false, error: 'Room not found' });
+    respond('join-room-response', { success: false, error: 'Room not found' });
     return;
   }
 
@@ -105,35 +149,82 @@ function joinRoom(ws, clientId, roomId) {
   // Update client's room
   const clientData = clients.get(clientId);
   clientData.roomId = roomId;
+  clientData.capacity = parseCapacity(capacity);
+  clientData.worker = worker || null;
+  clientData.available = false;
 
   console.log(`Client ${clientId} joined room: ${roomId}`);
 
   // Notify the room about the new client
   broadcastToRoom(roomId, 'client-joined', {
     clientId,
-    clientCount: room.clients.length
+    clientCount: room.clients.length,
+    member: memberInfo(clientId)
   });
 
-  sendToClient(ws, 'join-room-response', {
+  respond('join-room-response', {
     success: true,
     roomId,
     config: room.config,
     status: room.status,
-    clientCount: room.clients.length
+    clientCount: room.clients.length,
+    members: roomMembers(room)
   });
 }
 
+// Mark a client as an idle worker that a host can pull into its room
+function registerWorker(respond, clientId, { capacity, worker } = {}) {
+  leaveRoom(clientId);
+
+  const clientData = clients.get(clientId);
+  clientData.capacity = parseCapacity(capacity);
+  clientData.worker = worker || null;
+  clientData.available = true;
+
+  console.log(`Worker registered: ${clientId}`);
+  respond('register-worker-response', { success: true });
+}
+
+// Send idle workers to the host's room; each one joins it on receipt
+function assignWorkers(respond, clientId, { count } = {}) {
+  const clientData = clients.get(clientId);
+  if (!clientData || !clientData.roomId) {
+    respond('assign-workers-response', { success: false, error: 'Not in a room' });
+    return;
+  }
+
+  const room = rooms.get(clientData.roomId);
+  if (room.host !== clientId) {
+    respond('assign-workers-response', { success: false, error: 'Only the host can assign workers for the test' });
+    return;
+  }
+
+  const idle = [...clients.entries()]
+    .filter(([, data]) => data.available && !data.roomId)
+    .map(([id]) => id);
+  const assigned = count == null ? idle : idle.slice(0, count);
+
+  assigned.forEach(workerId => {
+    const workerData = clients.get(workerId);
+    workerData.available = false;
+    sendToClient(workerData.ws, 'room-assigned', { roomId: room.id });
+  });
+
+  console.log(`Assigned ${assigned.length} workers to room ${room.id}`);
+  respond('assign-workers-response', { success: true, assigned });
+}
+
 // Configure a test
-function configureTest(ws, clientId, config) {
+function configureTest(respond, clientId, config) {
   const clientData = clients.get(clientId);
   if (!clientData || !clientData.roomId) {
-    sendToClient(ws, 'configure-test-response', { success: false, error: 'Not in a room' });
+    respond('configure-test-response', { success: false, error: 'Not in a room' });
     return;
   }
 
   const room = rooms.get(clientData.roomId);
   if (room.host !== clientId) {
-    sendToClient(ws, 'configure-test-response', { success: false, error: 'Only the host can configure the test' });
+    respond('configure-test-response', { success: false, error: 'Only the host can configure the test' });
     return;
   }
 
@@ -144,25 +235,26 @@ function configureTest(ws, clientId, config) {
   broadcastToRoom(clientData.roomId, 'test-configured', config);
 
   console.log(`Test configured in room ${clientData.roomId}`);
-  sendToClient(ws, 'configure-test-response', { success: true });
+  respond('configure-test-response', { success: true });
 }
 
-// Start a test
-function startTest(ws, clientId) {
+// Start a test. The start is scheduled startDelayMs ahead so that every
+// client has the broadcast before anyone begins.
+function startTest(respond, clientId, startDelayMs = 0) {
   const clientData = clients.get(clientId);
   if (!clientData || !clientData.roomId) {
-    sendToClient(ws, 'start-test-response', { success: false, error: 'Not in a room' });
+    respond('start-test-response', { success: false, error: 'Not in a room' });
     return;
   }
 
   const room = rooms.get(clientData.roomId);
   if (room.host !== clientId) {
-    sendToClient(ws, 'start-test-response', { success: false, error: 'Only the host can start the test' });
+    respond('start-test-response', { success: false, error: 'Only the host can start the test' });
     return;
   }
 
   if (room.status !== 'configured') {
-    sendToClient(ws, 'start-test-response', { success: false, error: 'Test not configured' });
+    respond('start-test-response', { success: false, error: 'Test not configured' });
     return;
   }
 
@@ -172,24 +264,169 @@ function startTest(ws, clientId) {
   testResults.set(clientData.roomId, {
     startTime: Date.now(),
     clientResults: new Map(),
-    aggregated: null
+    // Reasons given by clients that won't run this test, keyed by client
+    declined: new Map(),
+    aggregated: null,
+    aborted: false
   });
 
-  // Notify all clients to start the test
-  broadcastToRoom(clientData.roomId, 'test-started', {
-    startTime: Date.now(),
-    config: room.config
-  });
+  // Each client gets its own share of the configured load
+  const startTime = Date.now() + Math.max(0, Number(startDelayMs) || 0);
+  for (const [workerId, config] of partitionLoad(room)) {
+    const workerData = clients.get(workerId);
+    if (workerData) {
+      sendToClient(workerData.ws, 'test-started', { startTime, config });
+    }
+  }
 
   console.log(`Test started in room ${clientData.roomId}`);
-  sendToClient(ws, 'start-test-response', { success: true });
+  respond('start-test-response', { success: true });
+}
+
+// Stop every worker in the room. Sent by the host, or by a worker whose abort
+// conditions tripped; only the first abort of a run is broadcast.
+function abortTest(respond, clientId, reason) {
+  const clientData = clients.get(clientId);
+  if (!clientData || !clientData.roomId) {
+    respond('abort-test-response', { success: false, error: 'Not in a room' });
+    return;
+  }
+
+  const room = rooms.get(clientData.roomId);
+  const testResult = testResults.get(clientData.roomId);
+  if (room.status !== 'running' || !testResult) {
+    respond('abort-test-response', { success: false, error: 'No test running' });
+    return;
+  }
+
+  if (!testResult.aborted) {
+    testResult.aborted = true;
+    broadcastToRoom(clientData.roomId, 'test-aborted', { clientId, reason: reason || 'Aborted' });
+    console.log(`Test aborted in room ${clientData.roomId} by ${clientId}: ${reason}`);
+  }
+  respond('abort-test-response', { success: true });
+}
+
+// What the room knows about a client, in the shape of the Rust RoomMember type
+function memberInfo(clientId) {
+  const clientData = clients.get(clientId);
+  return {
+    client_id: clientId,
+    capacity: clientData?.capacity ?? 1,
+    worker: clientData?.worker ?? null
+  };
+}
+
+function roomMembers(room) {
+  return room.clients.map(memberInfo);
+}
+
+// Capacities are relative weights; anything unusable counts as 1
+function parseCapacity(capacity) {
+  const value = Number(capacity);
+  return Number.isFinite(value) && value >= 0 ? value : 1;
+}
+
+// Splits an integer total in proportion to the weights, handing the rounding
+// remainder to the largest fractional shares so the parts add up exactly
+function splitByWeight(total, weights) {
+  const weightSum = weights.reduce((sum, weight) => sum + weight, 0);
+  const exact = weights.map(weight =>
+    weightSum > 0 ? (total * weight) / weightSum : total / weights.length
+  );
+  const shares = exact.map(Math.floor);
+
+  let remainder = total - shares.reduce((sum, share) => sum + share, 0);
+  const byFraction = exact
+    .map((value, index) => [value - shares[index], index])
+    .sort((a, b) => b[0] - a[0]);
+  for (const [, index] of byFraction) {
+    if (remainder <= 0) break;
+    shares[index] += 1;
+    remainder -= 1;
+  }
+
+  return shares;
+}
+
+// Works out the config each client in the room runs. Without a load target
+// everyone runs the room config as is; with one, the target is divided in
+// proportion to each client's declared capacity.
+function partitionLoad(room) {
+  const config = room.config;
+  const target = config.load_target;
+  const assignments = new Map();
+
+  if (!target) {
+    room.clients.forEach(clientId => assignments.set(clientId, config));
+    return assignments;
+  }
+
+  const weights = room.clients.map(clientId => clients.get(clientId)?.capacity ?? 1);
+  const weightSum = weights.reduce((sum, weight) => sum + weight, 0);
+  const fraction = index =>
+    weightSum > 0 ? weights[index] / weightSum : 1 / weights.length;
+
+  // Clients whose share rounds down to nothing still get a config, so they
+  // report back and the room can complete
+  const idle = {
+    ...config,
+    requests_per_client: 0,
+    concurrency: 1,
+    duration_ms: null,
+    rate_per_second: null
+  };
+
+  let shares;
+  switch (target.type) {
+    case 'requests': {
+      const requests = splitByWeight(Math.max(0, Math.floor(target.total)), weights);
+      shares = requests.map(count => count === 0 ? idle : {
+        ...config,
+        requests_per_client: count,
+        concurrency: Math.min(Math.max(config.concurrency, 1), count),
+        duration_ms: null
+      });
+      break;
+    }
+    case 'rps': {
+      // Without a duration, request counts scale with the rate so every
+      // client finishes at about the same time
+      const requests = splitByWeight(config.requests_per_client * room.clients.length, weights);
+      shares = requests.map((count, index) => {
+        const rate = target.total * fraction(index);
+        if (rate <= 0 || (!config.duration_ms && count === 0)) return idle;
+        return {
+          ...config,
+          requests_per_client: count,
+          rate_per_second: rate
+        };
+      });
+      break;
+    }
+    case 'virtual_users': {
+      const perUser = Math.floor(config.requests_per_client / Math.max(config.concurrency, 1));
+      const users = splitByWeight(Math.max(0, Math.floor(target.total)), weights);
+      shares = users.map(count => count === 0 ? idle : {
+        ...config,
+        concurrency: count,
+        requests_per_client: perUser * count
+      });
+      break;
+    }
+    default:
+      shares = room.clients.map(() => config);
+  }
+
+  room.clients.forEach((clientId, index) => assignments.set(clientId, shares[index]));
+  return assignments;
 }
 
 // Submit test results
-function submitResults(ws, clientId, results) {
+function submitResults(respond, clientId, results, declined) {
   const clientData = clients.get(clientId);
   if (!clientData || !clientData.roomId) {
-    sendToClient(ws, 'submit-results-response', { success: false, error: 'Not in a room' });
+    respond('submit-results-response', { success: false, error: 'Not in a room' });
     return;
   }
 
@@ -197,19 +434,33 @@ function submitResults(ws, clientId, results) {
   const testResult = testResults.get(clientData.roomId);
 
   if (!testResult) {
-    sendToClient(ws, 'submit-results-response', { success: false, error: 'No test running' });
+    respond('submit-results-response', { success: false, error: 'No test running' });
     return;
   }
 
-  // Store the client's results
-  testResult.clientResults.set(clientId, results);
+  if (testResult.aggregated) {
+    respond('submit-results-response', { success: false, error: 'The test run has already completed' });
+    return;
+  }
 
-  console.log(`Results received from ${clientId} in room ${clientData.roomId}`);
+  // A decline carries no results, so it is kept out of the aggregate.
+  // Results that are already in win over a late decline.
+  if (declined) {
+    if (!testResult.clientResults.has(clientId)) {
+      testResult.declined.set(clientId, declined);
+      console.log(`${clientId} declined the test in room ${clientData.roomId}: ${declined}`);
+    }
+  } else {
+    testResult.declined.delete(clientId);
+    testResult.clientResults.set(clientId, results);
+    console.log(`Results received from ${clientId} in room ${clientData.roomId}`);
+  }
 
-  // Check if all clients have submitted results
-  if (testResult.clientResults.size === room.clients.length) {
+  // Check if every client has submitted results or declined
+  if (room.clients.every((id) => testResult.clientResults.has(id) || testResult.declined.has(id))) {
     // Aggregate results
     const aggregatedResults = aggregateResults(testResult.clientResults);
+    aggregatedResults.declined = Object.fromEntries(testResult.declined);
     testResult.aggregated = aggregatedResults;
     room.status = 'completed';
 
@@ -219,7 +470,74 @@ function submitResults(ws, clientId, results) {
     console.log(`Test completed in room ${clientData.roomId}`);
   }
 
-  sendToClient(ws, 'submit-results-response', { success: true });
+  respond('submit-results-response', { success: true });
+}
+
+// Merge a worker's interim metrics into the room's live view. Field names
+// match the Rust LiveMetrics type.
+function recordMetricsDelta(clientId, delta) {
+  const clientData = clients.get(clientId);
+  if (!clientData || !clientData.roomId || !delta) return;
+
+  const testResult = testResults.get(clientData.roomId);
+  if (!testResult || testResult.aggregated) return;
+
+  if (!testResult.live) {
+    testResult.live = {
+      total_requests: 0,
+      successful_requests: 0,
+      failed_requests: 0,
+      total_response_time: 0,
+      status_codes: {},
+      workers: {},
+      histogram: {},
+      rates: {},
+      lastElapsed: {},
+      broadcastPending: false
+    };
+  }
+  const live = testResult.live;
+
+  live.total_requests += delta.requests || 0;
+  live.successful_requests += delta.successful_requests || 0;
+  live.failed_requests += delta.failed_requests || 0;
+  live.total_response_time += delta.total_response_time || 0;
+  live.workers[clientId] = (live.workers[clientId] || 0) + (delta.requests || 0);
+
+  for (const [code, count] of Object.entries(delta.status_codes || {})) {
+    live.status_codes[code] = (live.status_codes[code] || 0) + count;
+  }
+  for (const [bucket, count] of delta.latency_histogram || []) {
+    live.histogram[bucket] = (live.histogram[bucket] || 0) + count;
+  }
+
+  const interval = (delta.elapsed || 0) - (live.lastElapsed[clientId] || 0);
+  live.lastElapsed[clientId] = delta.elapsed || 0;
+  if (interval > 0) {
+    live.rates[clientId] = ((delta.requests || 0) / interval) * 1000;
+  }
+
+  // Coalesce deltas from all workers into one broadcast per second
+  if (!live.broadcastPending) {
+    live.broadcastPending = true;
+    const roomId = clientData.roomId;
+    setTimeout(() => {
+      live.broadcastPending = false;
+      if (testResults.get(roomId) !== testResult || testResult.aggregated) return;
+
+      broadcastToRoom(roomId, 'live-metrics', {
+        worker_count: Object.keys(live.workers).length,
+        total_requests: live.total_requests,
+        successful_requests: live.successful_requests,
+        failed_requests: live.failed_requests,
+        total_response_time: live.total_response_time,
+        status_codes: live.status_codes,
+        throughput: Object.values(live.rates).reduce((sum, rate) => sum + rate, 0),
+        workers: live.workers,
+        latency_histogram: Object.entries(live.histogram).map(([bucket, count]) => [Number(bucket), count])
+      });
+    }, 1000);
+  }
 }
 
 // Helper function to handle a client leaving a room
@@ -259,9 +577,9 @@ function leaveRoom(clientId) {
 }
 
 // Helper function to send a message to a specific client
-function sendToClient(ws, type, payload) {
+function sendToClient(ws, type, payload, requestId) {
   if (ws.readyState === ws.OPEN) {
-    ws.send(JSON.stringify({ type, payload }));
+    ws.send(JSON.stringify(requestId ? { type, payload, requestId } : { type, payload }));
   }
 }
 
@@ -278,73 +596,176 @@ function broadcastToRoom(roomId, type, payload) {
   });
 }
 
-// Helper function to aggregate results from all clients
+// Helper function to aggregate results from all clients. Field names match
+// the Rust AggregatedResult type: a TestResult plus per-client details.
 function aggregateResults(clientResults) {
+  const results = [...clientResults.values()].filter(Boolean);
+  const aggregated = mergeResults(results);
+  aggregated.client_count = clientResults.size;
+  aggregated.client_clocks = {};
+
+  for (const [clientId, result] of clientResults) {
+    if (result && result.clock_offset !== undefined && result.clock_offset !== null) {
+      aggregated.client_clocks[clientId] = {
+        offset: result.clock_offset,
+        uncertainty: result.clock_uncertainty
+      };
+    }
+  }
+
+  // Warm-up requests are merged on their own so they stay out of the totals
+  const warmups = results.map((result) => result.warmup).filter(Boolean);
+  if (warmups.length > 0) {
+    aggregated.warmup = mergeResults(warmups);
+  }
+
+  // Burst and recovery windows line up across workers, which start together
+  const spikes = results.map((result) => result.spike).filter(Boolean);
+  if (spikes.length > 0) {
+    aggregated.spike = {
+      bursts: Math.max(...spikes.map((spike) => spike.bursts || 0)),
+      burst: mergeResults(spikes.map((spike) => spike.burst)),
+      recovery: mergeResults(spikes.map((spike) => spike.recovery))
+    };
+  }
+
+  return aggregated;
+}
+
+// Sums the counters of several TestResults and derives the averages
+function mergeResults(results) {
   const aggregated = {
-    totalRequests: 0,
-    successfulRequests: 0,
-    failedRequests: 0,
-    totalDuration: 0,
-    minResponseTime: Number.MAX_SAFE_INTEGER,
-    maxResponseTime: 0,
-    avgResponseTime: 0,
-    statusCodes: {},
+    total_requests: 0,
+    successful_requests: 0,
+    failed_requests: 0,
+    min_response_time: Number.MAX_SAFE_INTEGER,
+    max_response_time: 0,
+    avg_response_time: 0,
+    total_response_time: 0,
+    status_codes: {},
+    duration: 0,
     throughput: 0,
-    clientCount: clientResults.size,
-    timestamp: Date.now()
+    test_id: uuidv4(),
+    timestamp: Math.floor(Date.now() / 1000),
+    latency_histogram: [],
+    aborted: null
   };
+  const histogram = {};
+  // Coordinated-omission corrected latency, when the workers measured it
+  const correctedHistogram = {};
+  let correctedMax = 0;
+  let correctedCount = 0;
+
+  for (const result of results) {
+    aggregated.total_requests += result.total_requests || 0;
+    aggregated.successful_requests += result.successful_requests || 0;
+    aggregated.failed_requests += result.failed_requests || 0;
+    aggregated.total_response_time += result.total_response_time || 0;
+
+    // A finished run reports a min of 0 when nothing succeeded
+    if ((result.latency_histogram || []).length > 0 && result.min_response_time < aggregated.min_response_time) {
+      aggregated.min_response_time = result.min_response_time;
+    }
 
-  let totalResponseTime = 0;
-  let totalRequests = 0;
-
-  for (const [_, result] of clientResults) {
-    if (!result) continue;
-
-    aggregated.totalRequests += result.totalRequests || 0;
-    aggregated.successfulRequests += result.successfulRequests || 0;
-    aggregated.failedRequests += result.failedRequests || 0;
+    if (result.max_response_time && result.max_response_time > aggregated.max_response_time) {
+      aggregated.max_response_time = result.max_response_time;
+    }
 
-    if (result.minResponseTime && result.minResponseTime < aggregated.minResponseTime) {
-      aggregated.minResponseTime = result.minResponseTime;
+    // Aggregate status codes
+    for (const [code, count] of Object.entries(result.status_codes || {})) {
+      aggregated.status_codes[code] = (aggregated.status_codes[code] || 0) + count;
     }
 
-    if (result.maxResponseTime && result.maxResponseTime > aggregated.maxResponseTime) {
-      aggregated.maxResponseTime = result.maxResponseTime;
+    // Percentiles are derived by the clients from the merged histogram
+    for (const [bucket, count] of result.latency_histogram || []) {
+      histogram[bucket] = (histogram[bucket] || 0) + count;
     }
 
-    totalResponseTime += result.totalResponseTime || 0;
-    totalRequests += result.totalRequests || 0;
+    if (result.aborted && !aggregated.aborted) {
+      aggregated.aborted = result.aborted;
+    }
 
-    // Aggregate status codes
-    if (result.statusCodes && typeof result.statusCodes === 'object') {
-      for (const [code, count] of Object.entries(result.statusCodes)) {
-        if (!aggregated.statusCodes[code]) {
-          aggregated.statusCodes[code] = 0;
-        }
-        aggregated.statusCodes[code] += count;
+    if (result.corrected) {
+      correctedCount += 1;
+      const corrected = result.corrected;
+      correctedMax = Math.max(correctedMax, corrected.max_response_time || 0);
+      for (const [bucket, count] of corrected.latency_histogram || []) {
+        correctedHistogram[bucket] = (correctedHistogram[bucket] || 0) + count;
       }
     }
 
     // Use the longest duration for total duration
-    if (result.duration && result.duration > aggregated.totalDuration) {
-      aggregated.totalDuration = result.duration;
+    if (result.duration && result.duration > aggregated.duration) {
+      aggregated.duration = result.duration;
     }
   }
 
+  if (aggregated.min_response_time === Number.MAX_SAFE_INTEGER) {
+    aggregated.min_response_time = 0;
+  }
+  aggregated.latency_histogram = Object.entries(histogram).map(([bucket, count]) => [Number(bucket), count]);
+  const soaks = results.map((result) => result.soak).filter(Boolean);
+  if (soaks.length > 0) {
+    aggregated.soak = mergeSoak(soaks);
+  }
+  if (correctedCount > 0) {
+    aggregated.corrected = {
+      max_response_time: correctedMax,
+      latency_histogram: Object.entries(correctedHistogram).map(([bucket, count]) => [Number(bucket), count])
+    };
+  }
+
   // Calculate average response time
-  if (totalRequests > 0) {
-    aggregated.avgResponseTime = totalResponseTime / totalRequests;
+  if (aggregated.total_requests > 0) {
+    aggregated.avg_response_time = aggregated.total_response_time / aggregated.total_requests;
   }
 
   // Calculate throughput (requests per second)
-  if (aggregated.totalDuration > 0) {
-    aggregated.throughput = (aggregated.totalRequests / aggregated.totalDuration) * 1000;
+  if (aggregated.duration > 0) {
+    aggregated.throughput = (aggregated.total_requests / aggregated.duration) * 1000;
   }
 
   return aggregated;
 }
 
+// Soak reports are merged interval by interval, like the Rust TestResult::merge:
+// counts add up, p50 and p99 take the slowest worker's and trends are kept
+function mergeSoak(soaks) {
+  const merged = { intervals: [], trends: [], snapshot_file: null };
+
+  for (const soak of soaks) {
+    (soak.intervals || []).forEach((interval, index) => {
+      const mine = merged.intervals[index];
+      if (!mine) {
+        merged.intervals.push({ ...interval });
+        return;
+      }
+      mine.start = Math.min(mine.start, interval.start);
+      mine.end = Math.max(mine.end, interval.end);
+      mine.requests += interval.requests;
+      mine.failed_requests += interval.failed_requests;
+      mine.total_response_time += interval.total_response_time;
+      mine.p50_response_time = Math.max(mine.p50_response_time, interval.p50_response_time);
+      mine.p99_response_time = Math.max(mine.p99_response_time, interval.p99_response_time);
+    });
+    merged.trends.push(...(soak.trends || []));
+    merged.snapshot_file = merged.snapshot_file || soak.snapshot_file || null;
+  }
+
+  for (const interval of merged.intervals) {
+    const seconds = (interval.end - interval.start) / 1000;
+    interval.throughput = seconds > 0 ? interval.requests / seconds : 0;
+    if (interval.requests > 0) {
+      interval.avg_response_time = interval.total_response_time / interval.requests;
+      interval.error_rate = (interval.failed_requests / interval.requests) * 100;
+    }
+  }
+
+  return merged;
+}
+
 const PORT = process.env.PORT || 3001;
 httpServer.listen(PORT, () => {
   console.log(`Armandra coordination server running on port ${PORT}`);
+  console.warn('server/index.js is deprecated; run armandra-coordinator instead');
 });
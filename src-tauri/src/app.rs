@@ -0,0 +1,574 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::{broadcast, Mutex};
+
+use crate::coordinator::Coordinator;
+use crate::stress_test::{TestConfig, TestResult, run_stress_test};
+use crate::protocol::{RoomEvent, RoomMember, WorkerProfile};
+use crate::websocket::{ClockSync, ConnectionHealth, HeartbeatConfig, WebSocketClient};
+
+// How far ahead the coordinator schedules a distributed start
+const DEFAULT_START_DELAY_MS: u64 = 3000;
+
+// State management for the WebSocket client
+struct WebSocketState {
+    client: Arc<Mutex<Option<WebSocketClient>>>,
+    current_room: Arc<Mutex<Option<String>>>,
+    auto_run: Arc<Mutex<bool>>,
+    capacity: Arc<Mutex<u32>>,
+    profile: Arc<Mutex<WorkerProfile>>,
+}
+
+// A coordinator started from the app, for running rooms without a separate server
+struct CoordinatorState {
+    server: Mutex<Option<Coordinator>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ConnectResponse {
+    success: bool,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RoomResponse {
+    success: bool,
+    room_id: Option<String>,
+    error: Option<String>,
+    client_count: Option<u32>,
+    status: Option<String>,
+    members: Option<Vec<RoomMember>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CoordinatorResponse {
+    success: bool,
+    error: Option<String>,
+    url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AssignResponse {
+    success: bool,
+    error: Option<String>,
+    assigned: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ClockSyncResponse {
+    success: bool,
+    error: Option<String>,
+    clock: Option<ClockSync>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TestResponse {
+    success: bool,
+    error: Option<String>,
+    result: Option<TestResult>,
+}
+
+// Re-emit a client broadcast channel as a Tauri event until it closes
+fn emit_events<T>(app: AppHandle, event_name: &'static str, mut events: broadcast::Receiver<T>)
+where
+    T: Serialize + Clone + Send + 'static,
+{
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Err(e) = app.emit(event_name, &event) {
+                        log::warn!("Failed to emit {}: {}", event_name, e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("Dropped {} {} events", skipped, event_name);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+// Forward client broadcasts to the frontend until the client is dropped
+fn forward_room_events(app: AppHandle, client: &WebSocketClient) {
+    emit_events(app.clone(), "room-event", client.subscribe());
+    emit_events(app.clone(), "worker-event", client.subscribe_worker());
+    emit_events(app.clone(), "connection-health", client.subscribe_health());
+
+    // The greeting may have arrived before we subscribed
+    let client = client.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Some(client_id) = client.client_id().await {
+            let _ = app.emit("room-event", &RoomEvent::Connected { client_id, session_token: None });
+        }
+    });
+}
+
+// Connect to the WebSocket server
+#[tauri::command]
+async fn connect_to_server(
+    server_url: String,
+    heartbeat: Option<HeartbeatConfig>,
+    app: AppHandle,
+    ws_state: State<'_, WebSocketState>,
+) -> Result<ConnectResponse, String> {
+    let heartbeat = heartbeat.unwrap_or_default();
+    if let Err(e) = heartbeat.validate() {
+        return Ok(ConnectResponse {
+            success: false,
+            error: Some(e),
+        });
+    }
+
+    match WebSocketClient::new(&server_url, heartbeat).await {
+        Ok(client) => {
+            client.set_auto_run(*ws_state.auto_run.lock().await);
+            client.set_capacity(*ws_state.capacity.lock().await);
+            client.set_worker_profile(ws_state.profile.lock().await.clone()).await;
+            forward_room_events(app, &client);
+            let mut ws_client = ws_state.client.lock().await;
+            // Stop the previous connection from reconnecting behind our back
+            if let Some(previous) = ws_client.take() {
+                previous.disconnect();
+            }
+            *ws_client = Some(client);
+            Ok(ConnectResponse {
+                success: true,
+                error: None,
+            })
+        }
+        Err(e) => Ok(ConnectResponse {
+            success: false,
+            error: Some(e),
+        }),
+    }
+}
+
+// Create a new room
+#[tauri::command]
+async fn create_room(
+    ws_state: State<'_, WebSocketState>,
+) -> Result<RoomResponse, String> {
+    let ws_client = ws_state.client.lock().await;
+
+    if let Some(client) = &*ws_client {
+        match client.create_room().await {
+            Ok(room_info) => {
+                let mut current_room = ws_state.current_room.lock().await;
+                *current_room = Some(room_info.room_id.clone());
+
+                Ok(RoomResponse {
+                    success: true,
+                    room_id: Some(room_info.room_id),
+                    error: None,
+                    client_count: Some(room_info.client_count),
+                    status: Some(room_info.status),
+                    members: Some(room_info.members),
+                })
+            }
+            Err(e) => Ok(RoomResponse {
+                success: false,
+                room_id: None,
+                error: Some(e),
+                client_count: None,
+                status: None,
+                members: None,
+            }),
+        }
+    } else {
+        Ok(RoomResponse {
+            success: false,
+            room_id: None,
+            error: Some("Not connected to server".to_string()),
+            client_count: None,
+            status: None,
+            members: None,
+        })
+    }
+}
+
+// Join an existing room
+#[tauri::command]
+async fn join_room(
+    room_id: String,
+    ws_state: State<'_, WebSocketState>,
+) -> Result<RoomResponse, String> {
+    let ws_client = ws_state.client.lock().await;
+
+    if let Some(client) = &*ws_client {
+        match client.join_room(&room_id).await {
+            Ok(room_info) => {
+                let mut current_room = ws_state.current_room.lock().await;
+                *current_room = Some(room_id);
+
+                Ok(RoomResponse {
+                    success: true,
+                    room_id: Some(room_info.room_id),
+                    error: None,
+                    client_count: Some(room_info.client_count),
+                    status: Some(room_info.status),
+                    members: Some(room_info.members),
+                })
+            }
+            Err(e) => Ok(RoomResponse {
+                success: false,
+                room_id: None,
+                error: Some(e),
+                client_count: None,
+                status: None,
+                members: None,
+            }),
+        }
+    } else {
+        Ok(RoomResponse {
+            success: false,
+            room_id: None,
+            error: Some("Not connected to server".to_string()),
+            client_count: None,
+            status: None,
+            members: None,
+        })
+    }
+}
+
+// Configure a test
+#[tauri::command]
+async fn configure_test(
+    config: TestConfig,
+    ws_state: State<'_, WebSocketState>,
+) -> Result<ConnectResponse, String> {
+    let ws_client = ws_state.client.lock().await;
+
+    if let Some(client) = &*ws_client {
+        match client.configure_test(&config).await {
+            Ok(_) => Ok(ConnectResponse {
+                success: true,
+                error: None,
+            }),
+            Err(e) => Ok(ConnectResponse {
+                success: false,
+                error: Some(e),
+            }),
+        }
+    } else {
+        Ok(ConnectResponse {
+            success: false,
+            error: Some("Not connected to server".to_string()),
+        })
+    }
+}
+
+// Start a test
+#[tauri::command]
+async fn start_test(
+    start_delay_ms: Option<u64>,
+    ws_state: State<'_, WebSocketState>,
+) -> Result<ConnectResponse, String> {
+    let ws_client = ws_state.client.lock().await;
+
+    if let Some(client) = &*ws_client {
+        match client.start_test(start_delay_ms.unwrap_or(DEFAULT_START_DELAY_MS)).await {
+            Ok(_) => Ok(ConnectResponse {
+                success: true,
+                error: None,
+            }),
+            Err(e) => Ok(ConnectResponse {
+                success: false,
+                error: Some(e),
+            }),
+        }
+    } else {
+        Ok(ConnectResponse {
+            success: false,
+            error: Some("Not connected to server".to_string()),
+        })
+    }
+}
+
+// Send idle headless workers to the room we host
+#[tauri::command]
+async fn assign_workers(
+    count: Option<u32>,
+    ws_state: State<'_, WebSocketState>,
+) -> Result<AssignResponse, String> {
+    let ws_client = ws_state.client.lock().await;
+
+    if let Some(client) = &*ws_client {
+        match client.assign_workers(count).await {
+            Ok(assigned) => Ok(AssignResponse {
+                success: true,
+                error: None,
+                assigned: Some(assigned),
+            }),
+            Err(e) => Ok(AssignResponse {
+                success: false,
+                error: Some(e),
+                assigned: None,
+            }),
+        }
+    } else {
+        Ok(AssignResponse {
+            success: false,
+            error: Some("Not connected to server".to_string()),
+            assigned: None,
+        })
+    }
+}
+
+// Run a stress test locally
+#[tauri::command]
+async fn run_test(config: TestConfig) -> Result<TestResponse, String> {
+    match run_stress_test(config).await {
+        Ok(result) => Ok(TestResponse {
+            success: true,
+            error: None,
+            result: Some(result),
+        }),
+        Err(e) => Ok(TestResponse {
+            success: false,
+            error: Some(e),
+            result: None,
+        }),
+    }
+}
+
+// Submit test results to the server
+#[tauri::command]
+async fn submit_results(
+    results: TestResult,
+    ws_state: State<'_, WebSocketState>,
+) -> Result<ConnectResponse, String> {
+    let ws_client = ws_state.client.lock().await;
+
+    if let Some(client) = &*ws_client {
+        match client.submit_results(&results).await {
+            Ok(_) => Ok(ConnectResponse {
+                success: true,
+                error: None,
+            }),
+            Err(e) => Ok(ConnectResponse {
+                success: false,
+                error: Some(e),
+            }),
+        }
+    } else {
+        Ok(ConnectResponse {
+            success: false,
+            error: Some("Not connected to server".to_string()),
+        })
+    }
+}
+
+// Re-estimate the clock offset to the coordinator
+#[tauri::command]
+async fn sync_clock(
+    ws_state: State<'_, WebSocketState>,
+) -> Result<ClockSyncResponse, String> {
+    let ws_client = ws_state.client.lock().await;
+
+    if let Some(client) = &*ws_client {
+        match client.sync_clock().await {
+            Ok(clock) => Ok(ClockSyncResponse {
+                success: true,
+                error: None,
+                clock: Some(clock),
+            }),
+            Err(e) => Ok(ClockSyncResponse {
+                success: false,
+                error: Some(e),
+                clock: None,
+            }),
+        }
+    } else {
+        Ok(ClockSyncResponse {
+            success: false,
+            error: Some("Not connected to server".to_string()),
+            clock: None,
+        })
+    }
+}
+
+// Start an in-process coordinator; other machines connect to its address
+#[tauri::command]
+async fn start_coordinator(
+    listen_addr: Option<String>,
+    coordinator_state: State<'_, CoordinatorState>,
+) -> Result<CoordinatorResponse, String> {
+    let mut server = coordinator_state.server.lock().await;
+
+    if let Some(coordinator) = &*server {
+        return Ok(CoordinatorResponse {
+            success: true,
+            error: None,
+            url: Some(coordinator.url()),
+        });
+    }
+
+    match Coordinator::bind(listen_addr.as_deref().unwrap_or("0.0.0.0:3001")).await {
+        Ok(coordinator) => {
+            let url = coordinator.url();
+            *server = Some(coordinator);
+            Ok(CoordinatorResponse {
+                success: true,
+                error: None,
+                url: Some(url),
+            })
+        }
+        Err(e) => Ok(CoordinatorResponse {
+            success: false,
+            error: Some(e),
+            url: None,
+        }),
+    }
+}
+
+#[tauri::command]
+async fn stop_coordinator(
+    coordinator_state: State<'_, CoordinatorState>,
+) -> Result<ConnectResponse, String> {
+    if let Some(coordinator) = coordinator_state.server.lock().await.take() {
+        coordinator.shutdown();
+    }
+
+    Ok(ConnectResponse {
+        success: true,
+        error: None,
+    })
+}
+
+// Coordinator connection health: last-seen time and heartbeat round trip
+#[tauri::command]
+async fn get_connection_health(
+    ws_state: State<'_, WebSocketState>,
+) -> Result<ConnectionHealth, String> {
+    match &*ws_state.client.lock().await {
+        Some(client) => Ok(client.health().await),
+        None => Ok(ConnectionHealth::default()),
+    }
+}
+
+// Opt in or out of running room tests automatically when the host starts them
+#[tauri::command]
+async fn set_auto_run(
+    enabled: bool,
+    ws_state: State<'_, WebSocketState>,
+) -> Result<ConnectResponse, String> {
+    *ws_state.auto_run.lock().await = enabled;
+
+    if let Some(client) = &*ws_state.client.lock().await {
+        client.set_auto_run(enabled);
+    }
+
+    Ok(ConnectResponse {
+        success: true,
+        error: None,
+    })
+}
+
+// Declare how much of a room-wide load target this worker should take
+#[tauri::command]
+async fn set_capacity(
+    capacity: u32,
+    ws_state: State<'_, WebSocketState>,
+) -> Result<ConnectResponse, String> {
+    *ws_state.capacity.lock().await = capacity;
+
+    if let Some(client) = &*ws_state.client.lock().await {
+        client.set_capacity(capacity);
+    }
+
+    Ok(ConnectResponse {
+        success: true,
+        error: None,
+    })
+}
+
+// Set the label and concurrency hint this worker reports when joining a room
+#[tauri::command]
+async fn set_worker_profile(
+    profile: WorkerProfile,
+    ws_state: State<'_, WebSocketState>,
+) -> Result<ConnectResponse, String> {
+    *ws_state.profile.lock().await = profile.clone();
+
+    if let Some(client) = &*ws_state.client.lock().await {
+        client.set_worker_profile(profile).await;
+    }
+
+    Ok(ConnectResponse {
+        success: true,
+        error: None,
+    })
+}
+
+// Leave the current room
+#[tauri::command]
+async fn leave_room(
+    ws_state: State<'_, WebSocketState>,
+) -> Result<ConnectResponse, String> {
+    let ws_client = ws_state.client.lock().await;
+
+    if let Some(client) = &*ws_client {
+        match client.leave_room().await {
+            Ok(_) => {
+                let mut current_room = ws_state.current_room.lock().await;
+                *current_room = None;
+
+                Ok(ConnectResponse {
+                    success: true,
+                    error: None,
+                })
+            }
+            Err(e) => Ok(ConnectResponse {
+                success: false,
+                error: Some(e),
+            }),
+        }
+    } else {
+        Ok(ConnectResponse {
+            success: false,
+            error: Some("Not connected to server".to_string()),
+        })
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    // Initialize logging; RUST_LOG overrides the default level
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .manage(WebSocketState {
+            client: Arc::new(Mutex::new(None)),
+            current_room: Arc::new(Mutex::new(None)),
+            auto_run: Arc::new(Mutex::new(true)),
+            capacity: Arc::new(Mutex::new(1)),
+            profile: Arc::new(Mutex::new(WorkerProfile::default())),
+        })
+        .manage(CoordinatorState {
+            server: Mutex::new(None),
+        })
+        .invoke_handler(tauri::generate_handler![
+            connect_to_server,
+            create_room,
+            join_room,
+            configure_test,
+            start_test,
+            assign_workers,
+            run_test,
+            submit_results,
+            set_auto_run,
+            set_capacity,
+            set_worker_profile,
+            sync_clock,
+            get_connection_health,
+            start_coordinator,
+            stop_coordinator,
+            leave_room,
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,354 @@
+// Headless worker for load machines without a display. Joins a room on a
+// coordinator, or waits for a host to assign it one, then runs every test the
+// host starts and submits the results. Settings come from flags and/or a TOML
+// or JSON file; flags win.
+use clap::{Parser, ValueEnum};
+use log::kv::{Error, Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Deserialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use armandra_lib::protocol::{RoomEvent, WorkerProfile};
+use armandra_lib::websocket::{HeartbeatConfig, WebSocketClient, WorkerEvent};
+
+// Bounds on the wait between attempts to reach the coordinator at startup
+const CONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const CONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Parser, Debug)]
+#[command(name = "armandra-agent", version, about = "Headless Armandra worker")]
+struct Args {
+    /// Coordinator WebSocket URL, e.g. ws://10.0.0.5:3001
+    #[arg(long)]
+    server: Option<String>,
+    /// Room to join; without one the agent waits for a host to assign it
+    #[arg(long)]
+    room: Option<String>,
+    /// Name shown to the host instead of the hostname
+    #[arg(long)]
+    label: Option<String>,
+    /// Share of a room-wide load target, relative to the other workers
+    #[arg(long)]
+    capacity: Option<u32>,
+    /// Most virtual users this machine should be given
+    #[arg(long)]
+    max_concurrency: Option<u32>,
+    /// TOML or JSON file with any of the settings above
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Log output; json unless set
+    #[arg(long, value_enum)]
+    log_format: Option<LogFormat>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum LogFormat {
+    // One JSON object per line on stdout
+    Json,
+    // Human-readable lines on stderr
+    Text,
+}
+
+// Contents of the --config file; every field is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AgentConfig {
+    server: Option<String>,
+    room: Option<String>,
+    label: Option<String>,
+    capacity: Option<u32>,
+    max_concurrency: Option<u32>,
+    log_format: Option<LogFormat>,
+    heartbeat: Option<HeartbeatConfig>,
+}
+
+impl AgentConfig {
+    fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        let config: AgentConfig = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => serde_json::from_str(&text).map_err(|e| format!("Invalid config {}: {}", path.display(), e))?,
+            _ => toml::from_str(&text).map_err(|e| format!("Invalid config {}: {}", path.display(), e))?,
+        };
+        if let Some(heartbeat) = &config.heartbeat {
+            heartbeat
+                .validate()
+                .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        }
+        Ok(config)
+    }
+
+    fn with_args(self, args: Args) -> Self {
+        AgentConfig {
+            server: args.server.or(self.server),
+            room: args.room.or(self.room),
+            label: args.label.or(self.label),
+            capacity: args.capacity.or(self.capacity),
+            max_concurrency: args.max_concurrency.or(self.max_concurrency),
+            log_format: args.log_format.or(self.log_format),
+            heartbeat: self.heartbeat,
+        }
+    }
+}
+
+struct JsonLogger {
+    level: LevelFilter,
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut line = serde_json::Map::new();
+        line.insert(
+            "timestamp".to_string(),
+            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true).into(),
+        );
+        line.insert("level".to_string(), record.level().as_str().into());
+        line.insert("target".to_string(), record.target().into());
+        line.insert("message".to_string(), record.args().to_string().into());
+        let _ = record.key_values().visit(&mut JsonFields(&mut line));
+
+        let _ = writeln!(std::io::stdout().lock(), "{}", serde_json::Value::Object(line));
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+// Copies a record's key-values into the JSON line, keeping numbers as numbers
+struct JsonFields<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        let value = if let Some(value) = value.to_u64() {
+            value.into()
+        } else if let Some(value) = value.to_i64() {
+            value.into()
+        } else if let Some(value) = value.to_f64() {
+            value.into()
+        } else if let Some(value) = value.to_bool() {
+            value.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+// RUST_LOG may name a plain level; anything else falls back to info
+fn init_logging(format: LogFormat) {
+    match format {
+        LogFormat::Json => {
+            let level = std::env::var("RUST_LOG")
+                .ok()
+                .and_then(|level| level.parse().ok())
+                .unwrap_or(LevelFilter::Info);
+            if log::set_boxed_logger(Box::new(JsonLogger { level })).is_ok() {
+                log::set_max_level(level);
+            }
+        }
+        LogFormat::Text => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+        }
+    }
+}
+
+// Keeps trying until the coordinator is up, so agents can start before it
+async fn connect(server: &str, heartbeat: HeartbeatConfig) -> WebSocketClient {
+    let mut delay = CONNECT_INITIAL_DELAY;
+
+    loop {
+        match WebSocketClient::new(server, heartbeat).await {
+            Ok(client) => {
+                log::info!(server = server; "Connected to {}", server);
+                return client;
+            }
+            Err(e) => {
+                log::warn!(server = server, retry_ms = delay.as_millis() as u64; "{}", e);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(CONNECT_MAX_DELAY);
+            }
+        }
+    }
+}
+
+async fn join(client: &WebSocketClient, room_id: &str) -> Result<(), String> {
+    let room = client.join_room(room_id).await?;
+    log::info!(
+        room_id = room.room_id.as_str(), client_count = room.client_count, status = room.status.as_str();
+        "Joined room {}", room.room_id
+    );
+    Ok(())
+}
+
+async fn wait_for_assignment(client: &WebSocketClient) -> Result<(), String> {
+    client.register_worker().await?;
+    log::info!("Registered as an idle worker, waiting for a host to assign a room");
+    Ok(())
+}
+
+async fn handle_room_event(client: &WebSocketClient, assignable: bool, event: RoomEvent) {
+    match event {
+        RoomEvent::RoomAssigned { room_id } => {
+            log::info!(room_id = room_id.as_str(); "Assigned to room {}", room_id);
+            if let Err(e) = join(client, &room_id).await {
+                log::error!(room_id = room_id.as_str(); "Failed to join assigned room: {}", e);
+                if let Err(e) = wait_for_assignment(client).await {
+                    log::error!("Failed to register as a worker: {}", e);
+                }
+            }
+        }
+        RoomEvent::ClientJoined { client_id, client_count, .. } => {
+            log::info!(client_id = client_id.as_str(), client_count = client_count; "Client {} joined", client_id);
+        }
+        RoomEvent::ClientLeft { client_id, client_count } => {
+            log::info!(client_id = client_id.as_str(), client_count = client_count; "Client {} left", client_id);
+        }
+        RoomEvent::HostChanged { new_host } => {
+            // An agent left alone in a room would host it with nobody driving;
+            // assigned agents go back to the pool instead
+            if assignable && client.client_id().await.as_deref() == Some(new_host.as_str()) {
+                log::info!("The host left the room, returning to the idle pool");
+                if let Err(e) = wait_for_assignment(client).await {
+                    log::error!("Failed to register as a worker: {}", e);
+                }
+            } else {
+                log::info!(new_host = new_host.as_str(); "Host is now {}", new_host);
+            }
+        }
+        RoomEvent::TestConfigured(config) => {
+            log::info!(url = config.url.as_str(), concurrency = config.concurrency; "Test configured for {}", config.url);
+        }
+        RoomEvent::TestStarted(start) => {
+            log::info!(start_time = start.start_time, url = start.config.url.as_str(); "Test started");
+        }
+        RoomEvent::TestAborted { client_id, reason } => {
+            log::warn!(client_id = client_id.as_str(), reason = reason.as_str(); "Test aborted by {}: {}", client_id, reason);
+        }
+        RoomEvent::TestCompleted(aggregated) => {
+            let result = &aggregated.result;
+            log::info!(
+                test_id = result.test_id.as_str(),
+                client_count = aggregated.client_count,
+                total_requests = result.total_requests,
+                failed_requests = result.failed_requests,
+                throughput = result.throughput,
+                p99_ms = result.p99_response_time,
+                passed = result.verdict.as_ref().map(|verdict| verdict.passed),
+                aborted = result.aborted.as_deref();
+                "Room test completed: {} requests from {} workers", result.total_requests, aggregated.client_count
+            );
+        }
+        RoomEvent::Disconnected => log::warn!("Lost the coordinator connection, reconnecting"),
+        RoomEvent::Reconnected { room_id } => {
+            log::info!(room_id = room_id.as_deref().unwrap_or_default(); "Reconnected to the coordinator");
+        }
+        RoomEvent::Connected { .. } | RoomEvent::LiveMetrics(_) => {}
+    }
+}
+
+fn log_worker_event(event: WorkerEvent) {
+    match event {
+        WorkerEvent::Running(config) => {
+            log::info!(url = config.url.as_str(), concurrency = config.concurrency; "Running test against {}", config.url);
+        }
+        WorkerEvent::Finished(result) => {
+            log::info!(
+                test_id = result.test_id.as_str(),
+                total_requests = result.total_requests,
+                successful_requests = result.successful_requests,
+                failed_requests = result.failed_requests,
+                throughput = result.throughput,
+                avg_ms = result.avg_response_time;
+                "Finished test {}, submitting results", result.test_id
+            );
+        }
+        WorkerEvent::Failed(error) => log::error!("Test failed: {}", error),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = Args::parse();
+    let config = match args.config.take() {
+        Some(path) => match AgentConfig::load(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        None => AgentConfig::default(),
+    };
+    let settings = config.with_args(args);
+
+    init_logging(settings.log_format.unwrap_or(LogFormat::Json));
+
+    let server = match &settings.server {
+        Some(server) => server.clone(),
+        None => {
+            log::error!("No coordinator given; pass --server or set server in the config file");
+            std::process::exit(2);
+        }
+    };
+
+    let client = connect(&server, settings.heartbeat.unwrap_or_default()).await;
+    client.set_capacity(settings.capacity.unwrap_or(1).max(1));
+    client
+        .set_worker_profile(WorkerProfile {
+            label: settings.label.clone(),
+            max_concurrency: settings.max_concurrency,
+        })
+        .await;
+
+    // Subscribe before entering a room so no event is missed
+    let mut room_events = client.subscribe();
+    let mut worker_events = client.subscribe_worker();
+
+    let entered = match &settings.room {
+        Some(room_id) => join(&client, room_id).await,
+        None => wait_for_assignment(&client).await,
+    };
+    if let Err(e) = entered {
+        log::error!("{}", e);
+        client.disconnect();
+        std::process::exit(1);
+    }
+
+    let assignable = settings.room.is_none();
+    loop {
+        tokio::select! {
+            event = room_events.recv() => match event {
+                Ok(event) => handle_room_event(&client, assignable, event).await,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => log::warn!("Dropped {} room events", skipped),
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            event = worker_events.recv() => match event {
+                Ok(event) => log_worker_event(event),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => log::warn!("Dropped {} worker events", skipped),
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = tokio::signal::ctrl_c() => {
+                log::info!("Shutting down");
+                break;
+            }
+        }
+    }
+
+    let _ = client.leave_room().await;
+    client.disconnect();
+}
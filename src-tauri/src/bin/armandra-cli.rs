@@ -0,0 +1,354 @@
+// Runs a single local test from a config file, for scripts and CI pipelines.
+// Takes the same TestConfig the desktop app builds, as JSON, YAML or TOML.
+// With a `capacity_search` section it instead searches for the highest rate
+// that meets the thresholds.
+//
+// Exit status: 0 when the run completed, 1 when it failed, was aborted, no
+// request succeeded or a threshold wasn't met, 2 when the config couldn't be
+// loaded. A search exits with 1 when no rate passed. Ctrl-C stops the run
+// early and still prints what completed.
+use clap::Parser;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tokio::sync::mpsc;
+
+use armandra_lib::capacity::{run_capacity_search, CapacityReport, CapacitySearch, CapacityTrial};
+use armandra_lib::soak::SoakReport;
+use armandra_lib::stress_test::{run_stress_test_with_progress, AbortSignal, MetricsDelta, TestConfig, TestResult};
+use armandra_lib::thresholds::{self, Verdict};
+
+#[derive(Parser, Debug)]
+#[command(name = "armandra-cli", version, about = "Run an Armandra stress test from a config file")]
+struct Args {
+    /// TestConfig as .json, .yaml/.yml or .toml
+    config: PathBuf,
+    /// Also write the full result as JSON to this file
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Print the result as JSON instead of the summary table
+    #[arg(long)]
+    json: bool,
+    /// Don't report progress on stderr while the test runs
+    #[arg(long, short)]
+    quiet: bool,
+}
+
+// The file's contents: a TestConfig, plus optionally the search settings
+#[derive(Deserialize)]
+struct RunConfig {
+    #[serde(flatten)]
+    test: TestConfig,
+    #[serde(default)]
+    capacity_search: Option<CapacitySearch>,
+}
+
+fn load_config(path: &Path) -> Result<RunConfig, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => serde_json::from_str(&text).map_err(|e| e.to_string()),
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&text).map_err(|e| e.to_string()),
+        Some("toml") => toml::from_str(&text).map_err(|e| e.to_string()),
+        _ => Err("expected a .json, .yaml, .yml or .toml file".to_string()),
+    }
+    .map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    .and_then(|config: RunConfig| thresholds::validate(&config.test.thresholds).map(|_| config))
+}
+
+// One line a second; stderr so stdout stays clean for the summary
+fn report_progress(mut progress: mpsc::UnboundedReceiver<MetricsDelta>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let (mut requests, mut failed) = (0u64, 0u64);
+        while let Some(delta) = progress.recv().await {
+            requests += delta.requests as u64;
+            failed += delta.failed_requests as u64;
+            eprintln!(
+                "[{:>6.1}s] {} requests, {} failed, {:.1} req/s",
+                delta.elapsed / 1000.0,
+                requests,
+                failed,
+                requests as f64 / (delta.elapsed / 1000.0).max(0.001)
+            );
+        }
+    })
+}
+
+fn print_summary(config: &TestConfig, result: &TestResult) {
+    let error_rate = if result.total_requests > 0 {
+        result.failed_requests as f64 / result.total_requests as f64 * 100.0
+    } else {
+        0.0
+    };
+
+    // Coordinated-omission corrected figures go next to the measured ones
+    let latency = |measured: f64, corrected: Option<f64>| match corrected {
+        Some(corrected) => format!("{:.2} ms (corrected {:.2} ms)", measured, corrected),
+        None => format!("{:.2} ms", measured),
+    };
+    let corrected = result.corrected.as_ref();
+
+    let mut rows = vec![("Target".to_string(), format!("{} {}", config.method, config.url))];
+    if let Some(reason) = &result.aborted {
+        rows.push(("Aborted".to_string(), reason.clone()));
+    }
+    rows.extend([
+        ("Requests".to_string(), result.total_requests.to_string()),
+        ("Successful".to_string(), result.successful_requests.to_string()),
+        ("Failed".to_string(), format!("{} ({:.2}%)", result.failed_requests, error_rate)),
+        ("Duration".to_string(), format!("{:.2} s", result.duration / 1000.0)),
+        ("Throughput".to_string(), format!("{:.2} req/s", result.throughput)),
+        ("Latency min".to_string(), format!("{:.2} ms", result.min_response_time)),
+        ("Latency avg".to_string(), format!("{:.2} ms", result.avg_response_time)),
+        ("Latency p50".to_string(), latency(result.p50_response_time, corrected.map(|c| c.p50_response_time))),
+        ("Latency p90".to_string(), latency(result.p90_response_time, corrected.map(|c| c.p90_response_time))),
+        ("Latency p95".to_string(), latency(result.p95_response_time, corrected.map(|c| c.p95_response_time))),
+        ("Latency p99".to_string(), latency(result.p99_response_time, corrected.map(|c| c.p99_response_time))),
+        ("Latency max".to_string(), latency(result.max_response_time, corrected.map(|c| c.max_response_time))),
+    ]);
+
+    // Cold numbers, kept out of everything above
+    if let Some(warmup) = &result.warmup {
+        rows.extend([
+            ("Warm-up requests".to_string(), format!("{} ({} failed)", warmup.total_requests, warmup.failed_requests)),
+            ("Warm-up duration".to_string(), format!("{:.2} s", warmup.duration / 1000.0)),
+            ("Warm-up avg".to_string(), format!("{:.2} ms", warmup.avg_response_time)),
+            ("Warm-up p99".to_string(), format!("{:.2} ms", warmup.p99_response_time)),
+        ]);
+    }
+
+    // Burst and recovery requests, also counted in the totals above
+    if let Some(spike) = &result.spike {
+        rows.push(("Bursts".to_string(), spike.bursts.to_string()));
+        for (name, window) in [("Burst", &spike.burst), ("Recovery", &spike.recovery)] {
+            rows.extend([
+                (format!("{} requests", name), format!("{} ({} failed)", window.total_requests, window.failed_requests)),
+                (format!("{} throughput", name), format!("{:.2} req/s", window.throughput)),
+                (format!("{} p50", name), format!("{:.2} ms", window.p50_response_time)),
+                (format!("{} p99", name), format!("{:.2} ms", window.p99_response_time)),
+            ]);
+        }
+    }
+
+    let mut status_codes: Vec<_> = result.status_codes.iter().collect();
+    status_codes.sort();
+    for (code, count) in status_codes {
+        rows.push((format!("Status {}", code), count.to_string()));
+    }
+
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    println!("{:<width$}  Value", "Metric", width = width);
+    println!("{}  {}", "-".repeat(width), "-".repeat(24));
+    for (name, value) in rows {
+        println!("{:<width$}  {}", name, value, width = width);
+    }
+
+    if let Some(verdict) = &result.verdict {
+        print_verdict(verdict);
+    }
+    if let Some(soak) = &result.soak {
+        print_soak(soak);
+    }
+}
+
+fn print_soak(soak: &SoakReport) {
+    println!();
+    println!("Soak: {} intervals", soak.intervals.len());
+    if let Some(path) = &soak.snapshot_file {
+        println!("Snapshots: {}", path);
+    }
+    if soak.trends.is_empty() {
+        println!("No drift detected");
+    }
+    for trend in &soak.trends {
+        println!("DRIFT  {}", trend.description);
+    }
+}
+
+fn print_verdict(verdict: &Verdict) {
+    let width = verdict
+        .thresholds
+        .iter()
+        .map(|threshold| threshold.expression.len())
+        .chain(std::iter::once("Threshold".len()))
+        .max()
+        .unwrap_or(0);
+
+    println!();
+    println!("{:<width$}  {:<16}  Result", "Threshold", "Observed", width = width);
+    println!("{}  {}  {}", "-".repeat(width), "-".repeat(16), "-".repeat(6));
+    for threshold in &verdict.thresholds {
+        let observed = match threshold.observed {
+            Some(observed) => format!("{:.2} {}", observed, threshold.unit),
+            None => "no data".to_string(),
+        };
+        println!(
+            "{:<width$}  {:<16}  {}",
+            threshold.expression,
+            observed.trim_end(),
+            if threshold.passed { "pass" } else { "FAIL" },
+            width = width
+        );
+    }
+    println!();
+    println!("{}", if verdict.passed { "PASSED" } else { "FAILED" });
+}
+
+fn print_trials(report: &CapacityReport) {
+    println!("{:>12}  {:>12}  {:>12}  {:>8}  Result", "Target", "Achieved", "p99", "Errors");
+    println!("{}  {}  {}  {}  {}", "-".repeat(12), "-".repeat(12), "-".repeat(12), "-".repeat(8), "-".repeat(6));
+    for trial in &report.trials {
+        let result = &trial.result;
+        let error_rate = if result.total_requests > 0 {
+            result.failed_requests as f64 / result.total_requests as f64 * 100.0
+        } else {
+            0.0
+        };
+        println!(
+            "{:>6.1} req/s  {:>6.1} req/s  {:>9.2} ms  {:>7.2}%  {}",
+            trial.rate_per_second,
+            result.throughput,
+            result.p99_response_time,
+            error_rate,
+            if trial.passed { "pass" } else { "FAIL" }
+        );
+    }
+    println!();
+    match report.max_passing_rps {
+        Some(rate) => println!("Max sustainable rate: {:.1} req/s", rate),
+        None => println!("No rate met the thresholds"),
+    }
+}
+
+fn report_trial(trial: &CapacityTrial) {
+    if trial.passed {
+        eprintln!("{:.1} req/s: pass", trial.rate_per_second);
+    } else {
+        eprintln!("{:.1} req/s: FAIL, {}", trial.rate_per_second, trial.failures.join("; "));
+    }
+}
+
+fn write_output<T: serde::Serialize>(path: &Path, value: &T) {
+    let written = serde_json::to_string_pretty(value)
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+    }
+}
+
+async fn search(args: &Args, config: TestConfig, search: CapacitySearch, abort: AbortSignal) -> ExitCode {
+    let (trials_tx, mut trials_rx) = mpsc::unbounded_channel();
+    let progress = (!args.quiet).then(|| {
+        tokio::spawn(async move {
+            while let Some(trial) = trials_rx.recv().await {
+                report_trial(&trial);
+            }
+        })
+    });
+
+    let outcome = run_capacity_search(config, search, progress.is_some().then_some(trials_tx), abort).await;
+    if let Some(progress) = progress {
+        let _ = progress.await;
+    }
+
+    let report = match outcome {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Search failed: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(path) = &args.output {
+        write_output(path, &report);
+    }
+    if args.json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the report: {}", e),
+        }
+    } else {
+        print_trials(&report);
+    }
+
+    if let Some(reason) = &report.aborted {
+        eprintln!("Aborted: {}", reason);
+        return ExitCode::FAILURE;
+    }
+    if report.max_passing_rps.is_none() {
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let (config, capacity_search) = match load_config(&args.config) {
+        Ok(config) => (config.test, config.capacity_search),
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let abort = AbortSignal::new();
+    let interrupt = abort.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            interrupt.abort("Interrupted");
+        }
+    });
+
+    if let Some(capacity_search) = capacity_search {
+        return search(&args, config, capacity_search, abort).await;
+    }
+
+    let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+    let progress = if args.quiet {
+        None
+    } else {
+        Some(report_progress(progress_rx))
+    };
+    let outcome = run_stress_test_with_progress(config.clone(), progress.is_some().then_some(progress_tx), abort).await;
+    if let Some(progress) = progress {
+        let _ = progress.await;
+    }
+
+    let result = match outcome {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Test failed: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(path) = &args.output {
+        write_output(path, &result);
+    }
+
+    if args.json {
+        match serde_json::to_string_pretty(&result) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the result: {}", e),
+        }
+    } else {
+        print_summary(&config, &result);
+    }
+
+    if let Some(reason) = &result.aborted {
+        eprintln!("Aborted: {}", reason);
+        return ExitCode::FAILURE;
+    }
+    if result.successful_requests == 0 {
+        eprintln!("No request succeeded");
+        return ExitCode::FAILURE;
+    }
+    if result.verdict.as_ref().is_some_and(|verdict| !verdict.passed) {
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,21 @@
+// Standalone coordination server; a drop-in replacement for server/index.js.
+// Listens on the address given as the first argument, or 0.0.0.0:$PORT.
+#[tokio::main]
+async fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let port = std::env::var("PORT").unwrap_or_else(|_| "3001".to_string());
+    let addr = std::env::args().nth(1).unwrap_or_else(|| format!("0.0.0.0:{}", port));
+
+    let coordinator = match armandra_lib::Coordinator::bind(&addr).await {
+        Ok(coordinator) => coordinator,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Armandra coordination server running on {}", coordinator.local_addr());
+
+    let _ = tokio::signal::ctrl_c().await;
+    coordinator.shutdown();
+}
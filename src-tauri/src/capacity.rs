@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::stress_test::{run_stress_test_with_progress, AbortSignal, TestConfig, TestResult};
+
+// A trial that sends less than this share of its target rate didn't really
+// test that rate, so it can't pass
+const MIN_ACHIEVED_RATE: f64 = 0.9;
+
+// Finds the highest request rate the target sustains while the config's
+// thresholds hold, e.g. "p99 < 500ms" and "error_rate < 1%". Every trial is a
+// rate-limited run of `trial_ms`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CapacitySearch {
+    #[serde(default)]
+    pub mode: SearchMode,
+    #[serde(default = "default_start_rps")]
+    pub start_rps: f64,
+    #[serde(default = "default_max_rps")]
+    pub max_rps: f64,
+    // Step mode: added to the rate after each passing trial; defaults to
+    // start_rps
+    #[serde(default)]
+    pub step_rps: Option<f64>,
+    // Binary mode: stops once the highest pass and lowest failure are this close
+    #[serde(default = "default_resolution_rps")]
+    pub resolution_rps: f64,
+    #[serde(default = "default_trial_ms")]
+    pub trial_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    // start_rps, then start_rps + step_rps and so on until a trial fails
+    Step,
+    // Doubles the rate until a trial fails, then bisects between the highest
+    // pass and the lowest failure
+    #[default]
+    Binary,
+}
+
+fn default_start_rps() -> f64 {
+    10.0
+}
+
+fn default_max_rps() -> f64 {
+    100_000.0
+}
+
+fn default_resolution_rps() -> f64 {
+    10.0
+}
+
+fn default_trial_ms() -> u64 {
+    10_000
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CapacityTrial {
+    pub rate_per_second: f64,
+    pub passed: bool,
+    // Why the trial failed, one entry per broken threshold or other cause
+    pub failures: Vec<String>,
+    pub result: TestResult,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CapacityReport {
+    // None when not even the lowest rate tried passed
+    pub max_passing_rps: Option<f64>,
+    pub trials: Vec<CapacityTrial>,
+    // Set when the search was stopped before it finished
+    pub aborted: Option<String>,
+}
+
+impl CapacitySearch {
+    fn validate(&self, config: &TestConfig) -> Result<(), String> {
+        if config.thresholds.is_empty() {
+            return Err("A capacity search needs thresholds to judge each trial by".to_string());
+        }
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        if !positive(self.start_rps) || !positive(self.max_rps) || self.start_rps > self.max_rps {
+            return Err("Capacity search start_rps must be above zero and no more than max_rps".to_string());
+        }
+        if self.step_rps.is_some_and(|step| !positive(step)) || !positive(self.resolution_rps) {
+            return Err("Capacity search step_rps and resolution_rps must be above zero".to_string());
+        }
+        if self.trial_ms == 0 {
+            return Err("Capacity search trial_ms must be above zero".to_string());
+        }
+        Ok(())
+    }
+
+    // The rate for the next trial, or None when the search is over
+    fn next_rate(&self, rate: f64, passed: bool, highest_pass: Option<f64>, lowest_failure: Option<f64>) -> Option<f64> {
+        match self.mode {
+            SearchMode::Step => {
+                let next = rate + self.step_rps.unwrap_or(self.start_rps);
+                (passed && next <= self.max_rps).then_some(next)
+            }
+            SearchMode::Binary => match (highest_pass, lowest_failure) {
+                (_, None) => (rate < self.max_rps).then(|| (rate * 2.0).min(self.max_rps)),
+                // Even the first trial failed, so search downwards
+                (None, Some(failure)) => Some(failure / 2.0).filter(|next| *next >= self.resolution_rps),
+                (Some(pass), Some(failure)) => (failure - pass > self.resolution_rps).then(|| (pass + failure) / 2.0),
+            },
+        }
+    }
+}
+
+fn judge(rate: f64, result: &TestResult) -> Vec<String> {
+    let mut failures = Vec::new();
+    if let Some(reason) = &result.aborted {
+        failures.push(format!("Aborted: {}", reason));
+    }
+    for threshold in result.verdict.iter().flat_map(|verdict| &verdict.thresholds) {
+        if !threshold.passed {
+            let observed = match threshold.observed {
+                Some(observed) if threshold.unit.is_empty() => format!("{:.2}", observed),
+                Some(observed) => format!("{:.2} {}", observed, threshold.unit),
+                None => "no data".to_string(),
+            };
+            failures.push(format!("{} (observed {})", threshold.expression, observed));
+        }
+    }
+    if result.throughput < rate * MIN_ACHIEVED_RATE {
+        failures.push(format!("Only reached {:.1} of {:.1} req/s", result.throughput, rate));
+    }
+    failures
+}
+
+// Runs trials until the search settles. Each finished trial is also sent on
+// `trials`, if given. Tripping `abort` ends the current trial and the search.
+pub async fn run_capacity_search(
+    config: TestConfig,
+    search: CapacitySearch,
+    trials: Option<mpsc::UnboundedSender<CapacityTrial>>,
+    abort: AbortSignal,
+) -> Result<CapacityReport, String> {
+    search.validate(&config)?;
+
+    let mut report = CapacityReport {
+        max_passing_rps: None,
+        trials: Vec::new(),
+        aborted: None,
+    };
+    let mut lowest_failure: Option<f64> = None;
+    let mut next_rate = Some(search.start_rps);
+
+    while let Some(rate) = next_rate {
+        let mut trial_config = config.clone();
+        trial_config.rate_per_second = Some(rate);
+        trial_config.duration_ms = Some(search.trial_ms);
+        trial_config.load_target = None;
+
+        // A trial's own abort conditions only fail that trial, so it gets its
+        // own signal, tripped along with the search's
+        let trial_abort = AbortSignal::new();
+        let forward = {
+            let trial_abort = trial_abort.clone();
+            let mut stop = abort.subscribe();
+            tokio::spawn(async move {
+                if let Ok(reason) = stop.wait_for(|reason| reason.is_some()).await {
+                    trial_abort.abort(reason.clone().unwrap_or_default());
+                }
+            })
+        };
+        let outcome = run_stress_test_with_progress(trial_config, None, trial_abort).await;
+        forward.abort();
+        let result = outcome?;
+
+        if let Some(reason) = abort.reason() {
+            report.aborted = Some(reason);
+            break;
+        }
+
+        let failures = judge(rate, &result);
+        let passed = failures.is_empty();
+        if passed {
+            report.max_passing_rps = Some(report.max_passing_rps.map_or(rate, |highest| highest.max(rate)));
+        } else {
+            lowest_failure = Some(lowest_failure.map_or(rate, |lowest| lowest.min(rate)));
+        }
+
+        let trial = CapacityTrial {
+            rate_per_second: rate,
+            passed,
+            failures,
+            result,
+        };
+        if let Some(trials) = &trials {
+            let _ = trials.send(trial.clone());
+        }
+        report.trials.push(trial);
+
+        next_rate = search.next_rate(rate, passed, report.max_passing_rps, lowest_failure);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::thresholds::{ThresholdOutcome, Verdict};
+
+    fn search(mode: SearchMode) -> CapacitySearch {
+        CapacitySearch {
+            mode,
+            start_rps: 10.0,
+            max_rps: 100.0,
+            step_rps: Some(20.0),
+            resolution_rps: 10.0,
+            trial_ms: 1000,
+        }
+    }
+
+    #[test]
+    fn binary_search_halves_after_a_failed_first_trial() {
+        let search = CapacitySearch { start_rps: 100.0, ..search(SearchMode::Binary) };
+        assert_eq!(search.next_rate(100.0, false, None, Some(100.0)), Some(50.0));
+        assert_eq!(search.next_rate(50.0, false, None, Some(50.0)), Some(25.0));
+        assert_eq!(search.next_rate(25.0, false, None, Some(25.0)), Some(12.5));
+        // Halving again would go below the resolution
+        assert_eq!(search.next_rate(12.5, false, None, Some(12.5)), None);
+    }
+
+    #[test]
+    fn binary_search_bisects_until_the_resolution() {
+        let search = search(SearchMode::Binary);
+        assert_eq!(search.next_rate(60.0, false, Some(40.0), Some(60.0)), Some(50.0));
+        assert_eq!(search.next_rate(50.0, false, Some(40.0), Some(50.0)), None);
+        assert_eq!(search.next_rate(45.0, true, Some(45.0), Some(50.0)), None);
+    }
+
+    #[test]
+    fn rates_are_capped_at_max_rps() {
+        let binary = search(SearchMode::Binary);
+        assert_eq!(binary.next_rate(40.0, true, Some(40.0), None), Some(80.0));
+        assert_eq!(binary.next_rate(80.0, true, Some(80.0), None), Some(100.0));
+        assert_eq!(binary.next_rate(100.0, true, Some(100.0), None), None);
+
+        let step = search(SearchMode::Step);
+        assert_eq!(step.next_rate(70.0, true, Some(70.0), None), Some(90.0));
+        assert_eq!(step.next_rate(90.0, true, Some(90.0), None), None);
+        assert_eq!(step.next_rate(30.0, false, Some(10.0), Some(30.0)), None);
+    }
+
+    #[test]
+    fn step_defaults_to_the_start_rate() {
+        let search = CapacitySearch { step_rps: None, ..search(SearchMode::Step) };
+        assert_eq!(search.next_rate(10.0, true, Some(10.0), None), Some(20.0));
+    }
+
+    #[test]
+    fn judge_reports_every_cause() {
+        let passing = TestResult { throughput: 95.0, ..Default::default() };
+        assert!(judge(100.0, &passing).is_empty());
+
+        let outcome = |expression: &str, observed, unit: &str| ThresholdOutcome {
+            expression: expression.to_string(),
+            observed,
+            limit: 0.0,
+            unit: unit.to_string(),
+            passed: false,
+        };
+        let failing = TestResult {
+            throughput: 50.0,
+            aborted: Some("error rate".to_string()),
+            verdict: Some(Verdict {
+                passed: false,
+                thresholds: vec![outcome("p99 < 500ms", Some(812.5), "ms"), outcome("p50 < 100ms", None, "ms")],
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            judge(100.0, &failing),
+            vec![
+                "Aborted: error rate",
+                "p99 < 500ms (observed 812.50 ms)",
+                "p50 < 100ms (observed no data)",
+                "Only reached 50.0 of 100.0 req/s",
+            ]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+use flate2::write::GzEncoder;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+pub fn gzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .map_err(|e| format!("Failed to gzip request body: {}", e))
+}
+
+// Decoded bodies beyond this are treated as a decompression bomb
+const MAX_DECODED_BYTES: u64 = 1 << 30;
+
+// Size of a response body once its Content-Encoding is undone. The decoded
+// bytes are counted as they stream past rather than kept, so a small body
+// that expands enormously can't exhaust memory.
+pub fn decoded_len(content_encoding: &str, data: &[u8]) -> Result<u64, String> {
+    decoded_len_capped(content_encoding, data, MAX_DECODED_BYTES)
+}
+
+fn decoded_len_capped(content_encoding: &str, data: &[u8], limit: u64) -> Result<u64, String> {
+    let decoder = decoder(content_encoding, data)?;
+    let length = io::copy(&mut decoder.take(limit + 1), &mut io::sink())
+        .map_err(|e| format!("Failed to decode {} body: {}", content_encoding, e))?;
+    if length > limit {
+        return Err(format!("Decoded body is larger than {} bytes", limit));
+    }
+    Ok(length)
+}
+
+// Reader over the decoded body. Stacked codings such as "gzip, br" were
+// applied in the order listed, so they are undone last first. Only the
+// encodings we advertise in Accept-Encoding are handled.
+fn decoder<'a>(content_encoding: &str, data: &'a [u8]) -> Result<Box<dyn Read + 'a>, String> {
+    let mut reader: Box<dyn Read + 'a> = Box::new(data);
+    for encoding in content_encoding.split(',').map(str::trim).rev() {
+        reader = match encoding.to_ascii_lowercase().as_str() {
+            "identity" | "" => reader,
+            "gzip" | "x-gzip" => Box::new(MultiGzDecoder::new(reader)),
+            "deflate" => deflate_decoder(reader)
+                .map_err(|e| format!("Failed to decode deflate body: {}", e))?,
+            "br" => Box::new(brotli_decompressor::Decompressor::new(reader, 4096)),
+            "zstd" => Box::new(
+                zstd::stream::read::Decoder::new(reader).map_err(|e| format!("Failed to decode zstd body: {}", e))?,
+            ),
+            other => return Err(format!("Unsupported content encoding: {}", other)),
+        };
+    }
+    Ok(reader)
+}
+
+// HTTP deflate is zlib-wrapped, but some servers send it raw; a zlib header
+// is a deflate method nibble with a check value divisible by 31
+fn deflate_decoder<'a>(reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let header = reader.fill_buf()?;
+    let zlib = header.len() >= 2
+        && header[0] & 0x0f == 8
+        && (u16::from(header[0]) << 8 | u16::from(header[1])) % 31 == 0;
+    Ok(if zlib {
+        Box::new(ZlibDecoder::new(reader))
+    } else {
+        Box::new(DeflateDecoder::new(reader))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{DeflateEncoder, ZlibEncoder};
+
+    const BODY: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+    fn decode(content_encoding: &str, data: &[u8]) -> Vec<u8> {
+        let mut decoded = Vec::new();
+        decoder(content_encoding, data).unwrap().read_to_end(&mut decoded).unwrap();
+        decoded
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn deflate_accepts_zlib_and_raw_streams() {
+        assert_eq!(decode("deflate", &zlib(BODY)), BODY);
+
+        let mut raw = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        raw.write_all(BODY).unwrap();
+        assert_eq!(decode("deflate", &raw.finish().unwrap()), BODY);
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        assert_eq!(decode("gzip", &gzip(BODY).unwrap()), BODY);
+        assert_eq!(decoded_len("GZIP", &gzip(BODY).unwrap()).unwrap(), BODY.len() as u64);
+    }
+
+    #[test]
+    fn stacked_encodings_are_undone_in_reverse() {
+        // deflate was applied first, then gzip
+        let stacked = gzip(&zlib(BODY)).unwrap();
+        assert_eq!(decode("deflate, gzip", &stacked), BODY);
+        assert_eq!(decoded_len("deflate,gzip", &stacked).unwrap(), BODY.len() as u64);
+        assert!(decoded_len("gzip, deflate", &stacked).is_err());
+        assert!(decoder("gzip, compress", &stacked).is_err());
+    }
+
+    #[test]
+    fn bodies_expanding_past_the_cap_fail() {
+        let bomb = gzip(&vec![0u8; 1 << 20]).unwrap();
+        assert!(bomb.len() < 4096);
+        assert_eq!(decoded_len_capped("gzip", &bomb, 1 << 20).unwrap(), 1 << 20);
+        assert!(decoded_len_capped("gzip", &bomb, (1 << 20) - 1).is_err());
+    }
+}
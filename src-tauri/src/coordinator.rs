@@ -0,0 +1,941 @@
+use futures_util::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio_tungstenite::{accept_async, tungstenite::protocol::Message};
+use uuid::Uuid;
+
+use crate::protocol::{
+    AbortRequest, AggregatedResult, AssignReply, AssignRequest, ClientClock, ClockSyncReply, ClockSyncRequest, ConfigureRequest, JoinRequest, LiveMetrics,
+    MetricsDeltaReport, ResumeReply, ResumeRequest, RoomEvent, RoomInfo, RoomMember, StartRequest, SubmitResultsRequest, TestStartInfo,
+    WebSocketMessage, WorkerInfo,
+};
+use crate::stress_test::{LoadTarget, MetricsDelta, TestConfig, TestResult};
+
+// Live metrics are coalesced into at most one broadcast per interval
+const LIVE_METRICS_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long a room member's place is held after its connection drops, so it
+// can reconnect and resume without the run completing in its absence
+pub const DISCONNECT_GRACE: Duration = Duration::from_secs(30);
+
+// Room coordinator speaking the same protocol as server/index.js. It runs in
+// the background until `shutdown` is called or it is dropped.
+pub struct Coordinator {
+    local_addr: SocketAddr,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Coordinator {
+    // Binding to port 0 picks a free port; `local_addr` tells which
+    pub async fn bind(addr: &str) -> Result<Self, String> {
+        Self::bind_with_grace(addr, DISCONNECT_GRACE).await
+    }
+
+    // Like `bind`, holding a dropped room member's place for `grace`
+    pub async fn bind_with_grace(addr: &str, grace: Duration) -> Result<Self, String> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to read listening address: {}", e))?;
+
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let state = Arc::new(Mutex::new(State::default()));
+        tokio::spawn(accept_connections(listener, state, grace, shutdown_rx));
+
+        log::info!("Coordinator listening on {}", local_addr);
+        Ok(Coordinator { local_addr, shutdown })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    // Address clients on this machine can connect to
+    pub fn url(&self) -> String {
+        let mut addr = self.local_addr;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(Ipv4Addr::LOCALHOST.into());
+        }
+        format!("ws://{}", addr)
+    }
+
+    // Stops accepting connections and closes the open ones
+    pub fn shutdown(&self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+#[derive(Default)]
+struct State {
+    next_client_id: u64,
+    clients: HashMap<String, Client>,
+    rooms: HashMap<String, Room>,
+}
+
+struct Client {
+    tx: mpsc::UnboundedSender<Message>,
+    // Proves a reconnecting client owns this session
+    session_token: String,
+    room_id: Option<String>,
+    capacity: u32,
+    worker: Option<WorkerInfo>,
+    // Registered as an idle worker, waiting to be assigned to a room
+    available: bool,
+}
+
+struct Room {
+    host: String,
+    clients: Vec<String>,
+    config: Option<TestConfig>,
+    status: RoomStatus,
+    run: Option<TestRun>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RoomStatus {
+    Waiting,
+    Configured,
+    Running,
+    Completed,
+}
+
+impl RoomStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RoomStatus::Waiting => "waiting",
+            RoomStatus::Configured => "configured",
+            RoomStatus::Running => "running",
+            RoomStatus::Completed => "completed",
+        }
+    }
+}
+
+// Everything collected while a room's test is running
+struct TestRun {
+    // Lets a delayed live-metrics broadcast tell whether its run is still current
+    id: String,
+    results: HashMap<String, TestResult>,
+    // Workers that reported they won't produce results, with their reason
+    declined: HashMap<String, String>,
+    live: LiveMetrics,
+    last_elapsed: HashMap<String, f64>,
+    rates: HashMap<String, f64>,
+    broadcast_pending: bool,
+    completed: bool,
+    // Only the first abort of a run is broadcast
+    aborted: bool,
+}
+
+impl TestRun {
+    fn new() -> Self {
+        TestRun {
+            id: Uuid::new_v4().to_string(),
+            results: HashMap::new(),
+            declined: HashMap::new(),
+            live: LiveMetrics::default(),
+            last_elapsed: HashMap::new(),
+            rates: HashMap::new(),
+            broadcast_pending: false,
+            completed: false,
+            aborted: false,
+        }
+    }
+}
+
+async fn accept_connections(
+    listener: TcpListener,
+    state: Arc<Mutex<State>>,
+    grace: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_connection(stream, Arc::clone(&state), grace, shutdown.clone()));
+                }
+                Err(e) => log::warn!("Failed to accept connection: {}", e),
+            },
+            // Also fires when the Coordinator is dropped
+            _ = shutdown.changed() => break,
+        }
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    state: Arc<Mutex<State>>,
+    grace: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let ws_stream = match accept_async(stream).await {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => {
+            log::warn!("WebSocket handshake failed: {}", e);
+            return;
+        }
+    };
+    let (mut write, mut read) = ws_stream.split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+
+    let session_token = Uuid::new_v4().to_string();
+    // Changes if the client resumes an earlier session
+    let mut client_id = {
+        let mut state = state.lock().await;
+        state.next_client_id += 1;
+        let client_id = format!("client-{}", state.next_client_id);
+        state.clients.insert(
+            client_id.clone(),
+            Client {
+                tx: tx.clone(),
+                session_token: session_token.clone(),
+                room_id: None,
+                capacity: 1,
+                worker: None,
+                available: false,
+            },
+        );
+        client_id
+    };
+    log::debug!("Client connected: {}", client_id);
+    send_event(
+        &tx,
+        &RoomEvent::Connected {
+            client_id: client_id.clone(),
+            session_token: Some(session_token),
+        },
+    );
+
+    loop {
+        tokio::select! {
+            outgoing = rx.recv() => match outgoing {
+                Some(message) => {
+                    if write.send(message).await.is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            },
+            incoming = read.next() => match incoming {
+                Some(Ok(Message::Text(text))) => handle_message(&state, &mut client_id, &tx, &text).await,
+                // The pong is queued by tungstenite; flushing sends it
+                Some(Ok(Message::Ping(_))) => {
+                    if write.flush().await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = shutdown.changed() => {
+                let _ = write.send(Message::Close(None)).await;
+                break;
+            }
+        }
+    }
+
+    log::debug!("Client disconnected: {}", client_id);
+    let mut guard = state.lock().await;
+    let in_room = match guard.clients.get(&client_id) {
+        // Resumed from another connection in the meantime
+        Some(client) if !client.tx.same_channel(&tx) => return,
+        Some(client) => client.room_id.is_some(),
+        None => return,
+    };
+    if !in_room || grace.is_zero() {
+        guard.remove_client(&client_id, &tx);
+        return;
+    }
+    drop(guard);
+
+    log::info!("Holding {}'s place for {:?}", client_id, grace);
+    tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
+        state.lock().await.remove_client(&client_id, &tx);
+    });
+}
+
+async fn handle_message(
+    state: &Arc<Mutex<State>>,
+    client_id: &mut String,
+    tx: &mpsc::UnboundedSender<Message>,
+    text: &str,
+) {
+    let received_at = now_ms();
+    let message = match serde_json::from_str::<WebSocketMessage>(text) {
+        Ok(message) => message,
+        Err(_) => {
+            send(tx, "error", serde_json::json!({ "error": "Invalid message format" }), None);
+            return;
+        }
+    };
+
+    let mut guard = state.lock().await;
+    let reply = match message.message_type.as_str() {
+        "create-room" => parse(message.payload).and_then(|request| guard.create_room(client_id, request)),
+        "join-room" => parse(message.payload).and_then(|request| guard.join_room(client_id, request)),
+        "configure-test" => parse(message.payload).and_then(|request| guard.configure_test(client_id, request)),
+        "start-test" => parse(message.payload).and_then(|request| guard.start_test(client_id, request)),
+        "clock-sync" => parse::<ClockSyncRequest>(message.payload).and_then(|request| {
+            to_payload(&ClockSyncReply {
+                client_send_time: request.client_send_time,
+                server_receive_time: received_at,
+                server_send_time: now_ms(),
+            })
+        }),
+        "register-worker" => parse(message.payload).map(|request| guard.register_worker(client_id, request)),
+        "assign-workers" => parse(message.payload).and_then(|request| guard.assign_workers(client_id, request)),
+        "abort-test" => parse(message.payload).and_then(|request| guard.abort_test(client_id, request)),
+        "submit-results" => parse(message.payload).and_then(|request| guard.submit_results(client_id, request)),
+        "resume-session" => parse(message.payload).and_then(|request| {
+            let reply = guard.resume_session(client_id, tx, request)?;
+            to_payload(&reply)
+        }),
+        "leave-room" => {
+            guard.leave_room(client_id);
+            if let Some(client) = guard.clients.get_mut(client_id) {
+                client.available = false;
+            }
+            Ok(serde_json::json!({}))
+        }
+        // Fire-and-forget; there is no response
+        "metrics-delta" => {
+            if let Ok(report) = parse::<MetricsDeltaReport>(message.payload) {
+                if let Some((room_id, run_id)) = guard.record_metrics_delta(client_id, report.delta) {
+                    schedule_live_metrics(Arc::clone(state), room_id, run_id);
+                }
+            }
+            return;
+        }
+        _ => {
+            send(
+                tx,
+                "error",
+                serde_json::json!({ "error": "Unknown message type" }),
+                message.request_id,
+            );
+            return;
+        }
+    };
+    drop(guard);
+
+    let payload = match reply {
+        Ok(mut payload) => {
+            payload["success"] = serde_json::json!(true);
+            payload
+        }
+        Err(error) => serde_json::json!({ "success": false, "error": error }),
+    };
+    send(tx, &format!("{}-response", message.message_type), payload, message.request_id);
+}
+
+// Broadcasts the room's merged live view once the interval has passed, so
+// deltas from every worker arriving in the meantime share one broadcast
+fn schedule_live_metrics(state: Arc<Mutex<State>>, room_id: String, run_id: String) {
+    tokio::spawn(async move {
+        tokio::time::sleep(LIVE_METRICS_INTERVAL).await;
+
+        let mut state = state.lock().await;
+        let live = match state.rooms.get_mut(&room_id).and_then(|room| room.run.as_mut()) {
+            Some(run) if run.id == run_id && !run.completed => {
+                run.broadcast_pending = false;
+                run.live.clone()
+            }
+            _ => return,
+        };
+        state.broadcast(&room_id, &RoomEvent::LiveMetrics(live));
+    });
+}
+
+impl State {
+    // Drops a client unless it has since resumed on a different connection
+    fn remove_client(&mut self, client_id: &str, tx: &mpsc::UnboundedSender<Message>) {
+        match self.clients.get(client_id) {
+            Some(client) if client.tx.same_channel(tx) => {}
+            _ => return,
+        }
+        self.leave_room(client_id);
+        self.clients.remove(client_id);
+    }
+
+    // Moves the connection known as `client_id` onto an earlier session, which
+    // keeps its ID, room and host role
+    fn resume_session(
+        &mut self,
+        client_id: &mut String,
+        tx: &mpsc::UnboundedSender<Message>,
+        request: ResumeRequest,
+    ) -> Result<ResumeReply, String> {
+        if request.client_id == *client_id {
+            return Err("Session already active on this connection".to_string());
+        }
+        match self.clients.get(&request.client_id) {
+            Some(session) if session.session_token == request.session_token => {}
+            _ => return Err("Session not found or expired".to_string()),
+        }
+
+        self.leave_room(client_id);
+        self.clients.remove(client_id.as_str());
+        if let Some(session) = self.clients.get_mut(&request.client_id) {
+            session.tx = tx.clone();
+        }
+        log::info!("Client {} resumed as {}", client_id, request.client_id);
+        *client_id = request.client_id;
+
+        let room = self.room_of(client_id).and_then(|room_id| self.room_info(&room_id));
+        Ok(ResumeReply { room })
+    }
+
+    fn create_room(&mut self, client_id: &str, request: JoinRequest) -> Result<serde_json::Value, String> {
+        self.leave_room(client_id);
+
+        let room_id = Uuid::new_v4().to_string();
+        self.rooms.insert(
+            room_id.clone(),
+            Room {
+                host: client_id.to_string(),
+                clients: vec![client_id.to_string()],
+                config: None,
+                status: RoomStatus::Waiting,
+                run: None,
+            },
+        );
+        self.enter(client_id, &room_id, request);
+
+        log::info!("Room created: {} by {}", room_id, client_id);
+        self.room_info(&room_id)
+            .ok_or_else(|| "Room not found".to_string())
+            .and_then(|info| to_payload(&info))
+    }
+
+    fn join_room(&mut self, client_id: &str, request: JoinRequest) -> Result<serde_json::Value, String> {
+        let room_id = request.room_id.clone().unwrap_or_default();
+        if !self.rooms.contains_key(&room_id) {
+            return Err("Room not found".to_string());
+        }
+        self.leave_room(client_id);
+
+        if let Some(room) = self.rooms.get_mut(&room_id) {
+            room.clients.push(client_id.to_string());
+        }
+        self.enter(client_id, &room_id, request);
+
+        log::info!("Client {} joined room: {}", client_id, room_id);
+
+        // Notify the room about the new client
+        let event = RoomEvent::ClientJoined {
+            client_id: client_id.to_string(),
+            client_count: self.rooms.get(&room_id).map_or(0, |room| room.clients.len() as u32),
+            member: self.member(client_id),
+        };
+        self.broadcast(&room_id, &event);
+
+        self.room_info(&room_id)
+            .ok_or_else(|| "Room not found".to_string())
+            .and_then(|info| to_payload(&info))
+    }
+
+    fn enter(&mut self, client_id: &str, room_id: &str, request: JoinRequest) {
+        if let Some(client) = self.clients.get_mut(client_id) {
+            client.room_id = Some(room_id.to_string());
+            client.capacity = request.capacity;
+            client.worker = request.worker;
+            client.available = false;
+        }
+    }
+
+    fn register_worker(&mut self, client_id: &str, request: JoinRequest) -> serde_json::Value {
+        self.leave_room(client_id);
+
+        if let Some(client) = self.clients.get_mut(client_id) {
+            client.capacity = request.capacity;
+            client.worker = request.worker;
+            client.available = true;
+        }
+
+        log::info!("Worker registered: {}", client_id);
+        serde_json::json!({})
+    }
+
+    // Sends idle workers to the host's room; each one joins it on receipt
+    fn assign_workers(&mut self, client_id: &str, request: AssignRequest) -> Result<serde_json::Value, String> {
+        let room_id = self.hosted_room(client_id, "assign workers for")?;
+
+        let mut idle: Vec<&String> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| client.available && client.room_id.is_none())
+            .map(|(id, _)| id)
+            .collect();
+        idle.sort();
+        let count = request.count.map_or(idle.len(), |count| count as usize);
+        let assigned: Vec<String> = idle.into_iter().take(count).cloned().collect();
+
+        let event = RoomEvent::RoomAssigned { room_id: room_id.clone() };
+        for id in &assigned {
+            if let Some(client) = self.clients.get_mut(id) {
+                client.available = false;
+                send_event(&client.tx, &event);
+            }
+        }
+
+        log::info!("Assigned {} workers to room {}", assigned.len(), room_id);
+        to_payload(&AssignReply { assigned })
+    }
+
+    fn configure_test(&mut self, client_id: &str, request: ConfigureRequest) -> Result<serde_json::Value, String> {
+        let room_id = self.hosted_room(client_id, "configure")?;
+
+        if let Some(room) = self.rooms.get_mut(&room_id) {
+            room.config = Some(request.config.clone());
+            room.status = RoomStatus::Configured;
+        }
+
+        // Notify all clients in the room about the new configuration
+        self.broadcast(&room_id, &RoomEvent::TestConfigured(request.config));
+
+        log::info!("Test configured in room {}", room_id);
+        Ok(serde_json::json!({}))
+    }
+
+    // The start is scheduled start_delay_ms ahead so that every client has
+    // the message before anyone begins
+    fn start_test(&mut self, client_id: &str, request: StartRequest) -> Result<serde_json::Value, String> {
+        let room_id = self.hosted_room(client_id, "start")?;
+
+        let room = &self.rooms[&room_id];
+        let config = match (&room.config, room.status) {
+            (Some(config), RoomStatus::Configured) => config.clone(),
+            _ => return Err("Test not configured".to_string()),
+        };
+
+        // Each client gets its own share of the configured load
+        let weights: Vec<f64> = room
+            .clients
+            .iter()
+            .map(|id| self.clients.get(id).map_or(1.0, |client| client.capacity as f64))
+            .collect();
+        let assignments = partition_load(&config, &weights);
+        let start_time = now_ms() as u64 + request.start_delay_ms;
+
+        for (id, config) in room.clients.iter().zip(assignments) {
+            if let Some(client) = self.clients.get(id) {
+                send_event(&client.tx, &RoomEvent::TestStarted(TestStartInfo { start_time, config }));
+            }
+        }
+
+        if let Some(room) = self.rooms.get_mut(&room_id) {
+            room.status = RoomStatus::Running;
+            room.run = Some(TestRun::new());
+        }
+
+        log::info!("Test started in room {}", room_id);
+        Ok(serde_json::json!({}))
+    }
+
+    // Sent by the host, or by a worker whose abort conditions tripped
+    fn abort_test(&mut self, client_id: &str, request: AbortRequest) -> Result<serde_json::Value, String> {
+        let room_id = self.room_of(client_id).ok_or_else(|| "Not in a room".to_string())?;
+
+        let run = self
+            .rooms
+            .get_mut(&room_id)
+            .and_then(|room| room.run.as_mut())
+            .filter(|run| !run.completed)
+            .ok_or_else(|| "No test running".to_string())?;
+        if !run.aborted {
+            run.aborted = true;
+            log::info!("Test aborted in room {} by {}: {}", room_id, client_id, request.reason);
+            self.broadcast(
+                &room_id,
+                &RoomEvent::TestAborted {
+                    client_id: client_id.to_string(),
+                    reason: request.reason,
+                },
+            );
+        }
+
+        Ok(serde_json::json!({}))
+    }
+
+    fn submit_results(&mut self, client_id: &str, request: SubmitResultsRequest) -> Result<serde_json::Value, String> {
+        let room_id = self.room_of(client_id).ok_or_else(|| "Not in a room".to_string())?;
+
+        let run = self
+            .rooms
+            .get_mut(&room_id)
+            .and_then(|room| room.run.as_mut())
+            .ok_or_else(|| "No test running".to_string())?;
+        if run.completed {
+            return Err("The test run has already completed".to_string());
+        }
+        match request.declined {
+            // Results that are already in win over a late decline
+            Some(reason) => {
+                if !run.results.contains_key(client_id) {
+                    log::info!("{} declined the test in room {}: {}", client_id, room_id, reason);
+                    run.declined.insert(client_id.to_string(), reason);
+                }
+            }
+            None => {
+                run.declined.remove(client_id);
+                run.results.insert(client_id.to_string(), request.results);
+                log::debug!("Results received from {} in room {}", client_id, room_id);
+            }
+        }
+        self.complete_if_reported(&room_id);
+
+        Ok(serde_json::json!({}))
+    }
+
+    // Merges a worker's interim metrics into the room's live view. Returns the
+    // room and run to broadcast for when no broadcast is pending yet.
+    fn record_metrics_delta(&mut self, client_id: &str, delta: MetricsDelta) -> Option<(String, String)> {
+        let room_id = self.room_of(client_id)?;
+        let run = self.rooms.get_mut(&room_id)?.run.as_mut()?;
+        if run.completed {
+            return None;
+        }
+
+        let live = &mut run.live;
+        live.total_requests += delta.requests as u64;
+        live.successful_requests += delta.successful_requests as u64;
+        live.failed_requests += delta.failed_requests as u64;
+        live.total_response_time += delta.total_response_time;
+        *live.workers.entry(client_id.to_string()).or_insert(0) += delta.requests as u64;
+        for (code, count) in &delta.status_codes {
+            *live.status_codes.entry(code.clone()).or_insert(0) += *count as u64;
+        }
+        live.latency_histogram.merge(&delta.latency_histogram);
+
+        let previous = run.last_elapsed.insert(client_id.to_string(), delta.elapsed).unwrap_or(0.0);
+        let interval = delta.elapsed - previous;
+        if interval > 0.0 {
+            run.rates.insert(client_id.to_string(), (delta.requests as f64 / interval) * 1000.0);
+        }
+        live.worker_count = live.workers.len() as u32;
+        live.throughput = run.rates.values().sum();
+
+        if run.broadcast_pending {
+            return None;
+        }
+        run.broadcast_pending = true;
+        Some((room_id, run.id.clone()))
+    }
+
+    fn leave_room(&mut self, client_id: &str) {
+        let room_id = match self.clients.get_mut(client_id).and_then(|client| client.room_id.take()) {
+            Some(room_id) => room_id,
+            None => return,
+        };
+        let room = match self.rooms.get_mut(&room_id) {
+            Some(room) => room,
+            None => return,
+        };
+
+        // Remove client from the room
+        room.clients.retain(|id| id != client_id);
+        log::info!("Client {} left room: {}", client_id, room_id);
+
+        if room.clients.is_empty() {
+            self.rooms.remove(&room_id);
+            log::info!("Room deleted: {}", room_id);
+            return;
+        }
+
+        // If the host left, assign a new host
+        let new_host = if room.host == client_id {
+            room.host = room.clients[0].clone();
+            Some(room.host.clone())
+        } else {
+            None
+        };
+        let client_count = room.clients.len() as u32;
+
+        if let Some(new_host) = new_host {
+            log::info!("New host in room {}: {}", room_id, new_host);
+            self.broadcast(&room_id, &RoomEvent::HostChanged { new_host });
+        }
+        self.broadcast(
+            &room_id,
+            &RoomEvent::ClientLeft {
+                client_id: client_id.to_string(),
+                client_count,
+            },
+        );
+
+        // The remaining workers may all have reported already
+        self.complete_if_reported(&room_id);
+    }
+
+    // Aggregates and broadcasts the results once every client in the room has
+    // submitted theirs
+    fn complete_if_reported(&mut self, room_id: &str) {
+        let room = match self.rooms.get_mut(room_id) {
+            Some(room) => room,
+            None => return,
+        };
+        let run = match room.run.as_mut() {
+            Some(run) if !run.completed => run,
+            _ => return,
+        };
+        if !room
+            .clients
+            .iter()
+            .all(|id| run.results.contains_key(id) || run.declined.contains_key(id))
+        {
+            return;
+        }
+
+        run.completed = true;
+        room.status = RoomStatus::Completed;
+        let mut aggregated = aggregate_results(&run.results);
+        aggregated.declined = run.declined.clone();
+
+        self.broadcast(room_id, &RoomEvent::TestCompleted(aggregated));
+        log::info!("Test completed in room {}", room_id);
+    }
+
+    fn room_of(&self, client_id: &str) -> Option<String> {
+        self.clients.get(client_id).and_then(|client| client.room_id.clone())
+    }
+
+    fn hosted_room(&self, client_id: &str, action: &str) -> Result<String, String> {
+        let room_id = self.room_of(client_id).ok_or_else(|| "Not in a room".to_string())?;
+        match self.rooms.get(&room_id) {
+            Some(room) if room.host == client_id => Ok(room_id),
+            _ => Err(format!("Only the host can {} the test", action)),
+        }
+    }
+
+    fn member(&self, client_id: &str) -> Option<RoomMember> {
+        self.clients.get(client_id).map(|client| RoomMember {
+            client_id: client_id.to_string(),
+            capacity: client.capacity,
+            worker: client.worker.clone(),
+        })
+    }
+
+    fn room_info(&self, room_id: &str) -> Option<RoomInfo> {
+        self.rooms.get(room_id).map(|room| RoomInfo {
+            room_id: room_id.to_string(),
+            client_count: room.clients.len() as u32,
+            status: room.status.as_str().to_string(),
+            config: room.config.clone(),
+            members: room.clients.iter().filter_map(|id| self.member(id)).collect(),
+        })
+    }
+
+    fn broadcast(&self, room_id: &str, event: &RoomEvent) {
+        let room = match self.rooms.get(room_id) {
+            Some(room) => room,
+            None => return,
+        };
+        for id in &room.clients {
+            if let Some(client) = self.clients.get(id) {
+                send_event(&client.tx, event);
+            }
+        }
+    }
+}
+
+// Combines every worker's results into one; the duration is the longest any
+// worker ran, and likewise for the warm-up
+fn aggregate_results(results: &HashMap<String, TestResult>) -> AggregatedResult {
+    let mut merged = TestResult::default();
+    let mut duration: f64 = 0.0;
+    let mut warmup_duration: f64 = 0.0;
+    let mut burst_duration: f64 = 0.0;
+    let mut recovery_duration: f64 = 0.0;
+    let mut client_clocks = HashMap::new();
+
+    for (client_id, result) in results {
+        merged.merge(result);
+        duration = duration.max(result.duration);
+        if let Some(warmup) = &result.warmup {
+            warmup_duration = warmup_duration.max(warmup.duration);
+        }
+        if let Some(spike) = &result.spike {
+            burst_duration = burst_duration.max(spike.burst.duration);
+            recovery_duration = recovery_duration.max(spike.recovery.duration);
+        }
+
+        if let Some(offset) = result.clock_offset {
+            client_clocks.insert(
+                client_id.clone(),
+                ClientClock {
+                    offset,
+                    uncertainty: result.clock_uncertainty,
+                },
+            );
+        }
+    }
+    merged.finalize(duration);
+    if let Some(warmup) = merged.warmup.as_mut() {
+        warmup.finalize(warmup_duration);
+    }
+    if let Some(spike) = merged.spike.as_mut() {
+        spike.burst.finalize(burst_duration);
+        spike.recovery.finalize(recovery_duration);
+    }
+
+    AggregatedResult {
+        result: merged,
+        client_count: results.len() as u32,
+        client_clocks,
+        declined: HashMap::new(),
+    }
+}
+
+// Splits `total` in proportion to `weights`, handing the rounding remainder to
+// the largest fractional shares so the parts add up exactly
+fn split_by_weight(total: u32, weights: &[f64]) -> Vec<u32> {
+    let weight_sum: f64 = weights.iter().sum();
+    let exact: Vec<f64> = weights
+        .iter()
+        .map(|weight| {
+            if weight_sum > 0.0 {
+                total as f64 * weight / weight_sum
+            } else {
+                total as f64 / weights.len() as f64
+            }
+        })
+        .collect();
+    let mut shares: Vec<u32> = exact.iter().map(|value| value.floor() as u32).collect();
+
+    let mut remainder = total.saturating_sub(shares.iter().sum());
+    let mut by_fraction: Vec<usize> = (0..exact.len()).collect();
+    by_fraction.sort_by(|a, b| {
+        let fraction = |index: usize| exact[index] - shares[index] as f64;
+        fraction(*b).total_cmp(&fraction(*a))
+    });
+    for index in by_fraction {
+        if remainder == 0 {
+            break;
+        }
+        shares[index] += 1;
+        remainder -= 1;
+    }
+
+    shares
+}
+
+// Works out the config each client runs, in room order. Without a load target
+// everyone runs the room config as is; with one, the target is divided in
+// proportion to each client's declared capacity.
+fn partition_load(config: &TestConfig, weights: &[f64]) -> Vec<TestConfig> {
+    let target = match &config.load_target {
+        Some(target) => target,
+        None => return weights.iter().map(|_| config.clone()).collect(),
+    };
+
+    let weight_sum: f64 = weights.iter().sum();
+    let fraction = |index: usize| {
+        if weight_sum > 0.0 {
+            weights[index] / weight_sum
+        } else {
+            1.0 / weights.len() as f64
+        }
+    };
+
+    // Clients whose share rounds down to nothing still get a config, so they
+    // report back and the room can complete
+    let idle = TestConfig {
+        requests_per_client: 0,
+        concurrency: 1,
+        duration_ms: None,
+        rate_per_second: None,
+        ..config.clone()
+    };
+
+    match target {
+        LoadTarget::Requests { total } => split_by_weight(*total, weights)
+            .into_iter()
+            .map(|count| {
+                if count == 0 {
+                    return idle.clone();
+                }
+                TestConfig {
+                    requests_per_client: count,
+                    concurrency: config.concurrency.clamp(1, count),
+                    duration_ms: None,
+                    ..config.clone()
+                }
+            })
+            .collect(),
+        LoadTarget::Rps { total } => {
+            // Without a duration, request counts scale with the rate so every
+            // client finishes at about the same time
+            let requests = config.requests_per_client.saturating_mul(weights.len() as u32);
+            split_by_weight(requests, weights)
+                .into_iter()
+                .enumerate()
+                .map(|(index, count)| {
+                    let rate = total * fraction(index);
+                    if rate <= 0.0 || (config.duration_ms.is_none() && count == 0) {
+                        return idle.clone();
+                    }
+                    TestConfig {
+                        requests_per_client: count,
+                        rate_per_second: Some(rate),
+                        ..config.clone()
+                    }
+                })
+                .collect()
+        }
+        LoadTarget::VirtualUsers { total } => {
+            let per_user = config.requests_per_client / config.concurrency.max(1);
+            split_by_weight(*total, weights)
+                .into_iter()
+                .map(|users| {
+                    if users == 0 {
+                        return idle.clone();
+                    }
+                    TestConfig {
+                        concurrency: users,
+                        requests_per_client: per_user.saturating_mul(users),
+                        ..config.clone()
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+fn now_ms() -> f64 {
+    chrono::Utc::now().timestamp_micros() as f64 / 1000.0
+}
+
+fn parse<T: DeserializeOwned>(payload: serde_json::Value) -> Result<T, String> {
+    serde_json::from_value(payload).map_err(|e| format!("Invalid payload: {}", e))
+}
+
+fn to_payload<T: Serialize>(value: &T) -> Result<serde_json::Value, String> {
+    serde_json::to_value(value).map_err(|e| format!("Failed to serialize message: {}", e))
+}
+
+fn send(tx: &mpsc::UnboundedSender<Message>, message_type: &str, payload: serde_json::Value, request_id: Option<String>) {
+    let message = WebSocketMessage {
+        message_type: message_type.to_string(),
+        payload,
+        request_id,
+    };
+    if let Ok(text) = serde_json::to_string(&message) {
+        let _ = tx.send(Message::Text(text));
+    }
+}
+
+// Room events already serialize to the `{ type, payload }` envelope
+fn send_event(tx: &mpsc::UnboundedSender<Message>, event: &RoomEvent) {
+    if let Ok(text) = serde_json::to_string(event) {
+        let _ = tx.send(Message::Text(text));
+    }
+}
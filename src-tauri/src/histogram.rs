@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+
+// Log-linear latency histogram over microseconds. Values below 64µs get a
+// bucket each; above that every power of two is split into 32 buckets, which
+// keeps the relative error around 3% with a fixed memory footprint.
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+// Anything slower than an hour lands in the last bucket
+const MAX_VALUE_US: u64 = 3_600_000_000;
+
+fn bucket_index(value_us: u64) -> usize {
+    let value_us = value_us.min(MAX_VALUE_US);
+    if value_us < SUB_BUCKETS * 2 {
+        return value_us as usize;
+    }
+
+    let bits = 64 - value_us.leading_zeros();
+    let shift = bits - SUB_BUCKET_BITS - 1;
+    (shift as u64 * SUB_BUCKETS + (value_us >> shift)) as usize
+}
+
+// Smallest value in a bucket, in microseconds
+fn bucket_lower(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS * 2 {
+        return index;
+    }
+
+    let shift = index / SUB_BUCKETS - 1;
+    (index % SUB_BUCKETS + SUB_BUCKETS) << shift
+}
+
+// Midpoint of a bucket, in microseconds
+fn bucket_value(index: usize) -> f64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS * 2 {
+        return index as f64;
+    }
+
+    let shift = index / SUB_BUCKETS - 1;
+    let mantissa = index % SUB_BUCKETS + SUB_BUCKETS;
+    let lower = mantissa << shift;
+    let upper = (mantissa + 1) << shift;
+    (lower + upper) as f64 / 2.0
+}
+
+// Serialized sparsely as [bucket, count] pairs so deltas stay small on the wire
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<(u32, u64)>", into = "Vec<(u32, u64)>")]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, value_ms: f64) {
+        self.record_n(value_ms, 1);
+    }
+
+    pub fn record_n(&mut self, value_ms: f64, count: u64) {
+        if count == 0 {
+            return;
+        }
+        let index = bucket_index((value_ms.max(0.0) * 1000.0) as u64);
+        if self.counts.len() <= index {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += count;
+        self.total += count;
+    }
+
+    // Records a response that held up a user meant to send every
+    // `interval_ms`, plus the samples for the requests it kept from being
+    // sent, each waiting one interval less than the one before. The backfill
+    // is added a bucket at a time, so it costs the same however many samples
+    // a long stall produces.
+    pub fn record_corrected(&mut self, value_ms: f64, interval_ms: f64) {
+        self.record(value_ms);
+        if interval_ms.is_nan() || interval_ms <= 0.0 || !value_ms.is_finite() {
+            return;
+        }
+
+        // Samples are value_ms - k * interval_ms for k in 1..=missed, down to
+        // the last one still at least an interval long
+        let missed = ((value_ms / interval_ms).floor() as u64).saturating_sub(1);
+        let mut k = 1;
+        while k <= missed {
+            let sample_ms = value_ms - k as f64 * interval_ms;
+            let lower_ms = bucket_lower(bucket_index((sample_ms * 1000.0) as u64)) as f64 / 1000.0;
+            // The further samples that still fall in the same bucket
+            let last = (((value_ms - lower_ms) / interval_ms).floor() as u64).clamp(k, missed);
+            self.record_n(sample_ms, last - k + 1);
+            k = last + 1;
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (index, count) in other.counts.iter().enumerate() {
+            self.counts[index] += count;
+        }
+        self.total += other.total;
+    }
+
+    // Counts recorded since `earlier`, which must be a previous copy of self
+    pub fn delta_since(&self, earlier: &Histogram) -> Histogram {
+        let counts: Vec<u64> = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(index, count)| count.saturating_sub(earlier.counts.get(index).copied().unwrap_or(0)))
+            .collect();
+        let total = counts.iter().sum();
+        Histogram { counts, total }
+    }
+
+    // Value in milliseconds at the given percentile (0-100)
+    pub fn percentile(&self, percentile: f64) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return bucket_value(index) / 1000.0;
+            }
+        }
+
+        bucket_value(self.counts.len().saturating_sub(1)) / 1000.0
+    }
+}
+
+impl From<Vec<(u32, u64)>> for Histogram {
+    fn from(buckets: Vec<(u32, u64)>) -> Self {
+        let mut histogram = Histogram::new();
+        let max_index = bucket_index(MAX_VALUE_US);
+        for (index, count) in buckets {
+            let index = index as usize;
+            // Don't let a malformed peer make us allocate arbitrarily
+            if index > max_index {
+                continue;
+            }
+            if histogram.counts.len() <= index {
+                histogram.counts.resize(index + 1, 0);
+            }
+            histogram.counts[index] += count;
+            histogram.total += count;
+        }
+        histogram
+    }
+}
+
+impl From<Histogram> for Vec<(u32, u64)> {
+    fn from(histogram: Histogram) -> Self {
+        histogram
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(index, count)| (index as u32, *count))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The backfill one sample at a time, as a reference
+    fn corrected_by_sample(value_ms: f64, interval_ms: f64) -> Histogram {
+        let mut histogram = Histogram::new();
+        histogram.record(value_ms);
+        let mut missed = value_ms - interval_ms;
+        while missed >= interval_ms {
+            histogram.record(missed);
+            missed -= interval_ms;
+        }
+        histogram
+    }
+
+    #[test]
+    fn corrected_backfill_matches_sample_by_sample() {
+        for (value_ms, interval_ms) in [(5.0, 10.0), (25.0, 10.0), (1234.5, 1.0), (60_000.0, 7.3), (250.0, 0.25)] {
+            let mut histogram = Histogram::new();
+            histogram.record_corrected(value_ms, interval_ms);
+            assert_eq!(histogram, corrected_by_sample(value_ms, interval_ms), "{} every {}", value_ms, interval_ms);
+        }
+    }
+
+    #[test]
+    fn corrected_backfill_of_a_long_stall_is_bounded() {
+        let mut histogram = Histogram::new();
+        histogram.record_corrected(3_600_000.0, 1.0);
+        assert_eq!(histogram.count(), 3_600_000);
+        assert!(histogram.counts.len() <= bucket_index(MAX_VALUE_US) + 1);
+        assert!((histogram.percentile(50.0) - 1_800_000.0).abs() / 1_800_000.0 < 0.05);
+    }
+
+    #[test]
+    fn bucket_lower_bounds_their_bucket() {
+        for index in 0..bucket_index(MAX_VALUE_US) {
+            assert_eq!(bucket_index(bucket_lower(index)), index);
+            assert_eq!(bucket_index(bucket_lower(index + 1) - 1), index);
+        }
+    }
+}
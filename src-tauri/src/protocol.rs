@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use sysinfo::System;
+
+use crate::histogram::Histogram;
+use crate::stress_test::{MetricsDelta, TestConfig, TestResult};
+
+// Message types shared by the WebSocket client and the coordinator. The
+// camelCase renames keep the wire format the JavaScript coordinator speaks.
+
+// Reply to create-room and join-room
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoomInfo {
+    #[serde(rename = "roomId")]
+    pub room_id: String,
+    #[serde(rename = "clientCount")]
+    pub client_count: u32,
+    pub status: String,
+    pub config: Option<TestConfig>,
+    #[serde(default)]
+    pub members: Vec<RoomMember>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoomMember {
+    pub client_id: String,
+    #[serde(default)]
+    pub capacity: u32,
+    // None for clients that didn't describe themselves on join
+    #[serde(default)]
+    pub worker: Option<WorkerInfo>,
+}
+
+// What a worker reports about itself when it creates or joins a room
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkerInfo {
+    pub hostname: String,
+    pub os: String,
+    pub cpu_cores: u32,
+    // In bytes
+    pub available_memory: u64,
+    pub label: Option<String>,
+    pub app_version: String,
+    pub max_concurrency: Option<u32>,
+}
+
+// The parts of WorkerInfo the user chooses rather than detects
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct WorkerProfile {
+    pub label: Option<String>,
+    pub max_concurrency: Option<u32>,
+}
+
+impl WorkerInfo {
+    pub fn detect(profile: &WorkerProfile) -> Self {
+        let mut system = System::new();
+        system.refresh_memory();
+
+        let os = match (System::name(), System::os_version()) {
+            (Some(name), Some(version)) => format!("{} {}", name, version),
+            (Some(name), None) => name,
+            _ => std::env::consts::OS.to_string(),
+        };
+
+        WorkerInfo {
+            hostname: System::host_name().unwrap_or_default(),
+            os,
+            cpu_cores: std::thread::available_parallelism()
+                .map(|cores| cores.get() as u32)
+                .unwrap_or(1),
+            available_memory: system.available_memory(),
+            label: profile.label.clone(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            max_concurrency: profile.max_concurrency,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TestStartInfo {
+    #[serde(alias = "startTime")]
+    pub start_time: u64,
+    pub config: TestConfig,
+}
+
+// Envelope for every message in both directions
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebSocketMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub payload: serde_json::Value,
+    // Set on requests and echoed back on their responses; broadcasts have none
+    #[serde(rename = "requestId", default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+// Payload of create-room, join-room and register-worker; only join-room
+// carries a room ID
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JoinRequest {
+    #[serde(rename = "roomId", default, skip_serializing_if = "Option::is_none")]
+    pub room_id: Option<String>,
+    #[serde(default = "default_capacity")]
+    pub capacity: u32,
+    #[serde(default)]
+    pub worker: Option<WorkerInfo>,
+}
+
+fn default_capacity() -> u32 {
+    1
+}
+
+// Takes a dropped connection's place, room and host role included, while the
+// coordinator is still holding it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResumeRequest {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[serde(rename = "sessionToken")]
+    pub session_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResumeReply {
+    // None when the session isn't in a room
+    #[serde(default)]
+    pub room: Option<RoomInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfigureRequest {
+    pub config: TestConfig,
+}
+
+// Sent by a host to pull idle registered workers into its room
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AssignRequest {
+    // Every idle worker when not set
+    #[serde(default)]
+    pub count: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AssignReply {
+    pub assigned: Vec<String>,
+}
+
+// Stops the room's running test on every worker
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AbortRequest {
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StartRequest {
+    #[serde(rename = "startDelayMs", default)]
+    pub start_delay_ms: u64,
+}
+
+// Times are Unix milliseconds on the respective machine's clock
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClockSyncRequest {
+    #[serde(rename = "clientSendTime")]
+    pub client_send_time: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClockSyncReply {
+    #[serde(rename = "clientSendTime")]
+    pub client_send_time: f64,
+    #[serde(rename = "serverReceiveTime")]
+    pub server_receive_time: f64,
+    #[serde(rename = "serverSendTime")]
+    pub server_send_time: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubmitResultsRequest {
+    pub results: TestResult,
+    // Set when the worker skipped or failed the run instead of producing
+    // results, so the room doesn't wait for it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub declined: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MetricsDeltaReport {
+    pub delta: MetricsDelta,
+}
+
+// Merged view of the metric deltas streamed by every worker in the room
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LiveMetrics {
+    pub worker_count: u32,
+    pub total_requests: u64,
+    pub successful_requests: u64,
+    pub failed_requests: u64,
+    pub total_response_time: f64,
+    pub status_codes: HashMap<String, u64>,
+    // Combined requests per second over each worker's latest interval
+    pub throughput: f64,
+    // Requests completed so far, per worker
+    pub workers: HashMap<String, u64>,
+    pub latency_histogram: Histogram,
+    // Filled in locally from the histogram
+    #[serde(default)]
+    pub p50_response_time: f64,
+    #[serde(default)]
+    pub p95_response_time: f64,
+    #[serde(default)]
+    pub p99_response_time: f64,
+}
+
+// Every worker's results merged into one, broadcast when the last of them
+// has reported
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AggregatedResult {
+    #[serde(flatten)]
+    pub result: TestResult,
+    pub client_count: u32,
+    #[serde(default)]
+    pub client_clocks: HashMap<String, ClientClock>,
+    // Workers that didn't run the test, with their reason
+    #[serde(default)]
+    pub declined: HashMap<String, String>,
+}
+
+// A worker's clock offset from the coordinator, as it reported it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClientClock {
+    pub offset: f64,
+    pub uncertainty: Option<f64>,
+}
+
+// Broadcasts the coordinator sends to everyone in a room. The wire format is
+// the same `{ type, payload }` envelope as every other message.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", content = "payload", rename_all = "kebab-case")]
+pub enum RoomEvent {
+    Connected {
+        #[serde(alias = "clientId")]
+        client_id: String,
+        // Presented with client_id to resume the session after a reconnect
+        #[serde(default, alias = "sessionToken", skip_serializing_if = "Option::is_none")]
+        session_token: Option<String>,
+    },
+    ClientJoined {
+        #[serde(alias = "clientId")]
+        client_id: String,
+        #[serde(alias = "clientCount")]
+        client_count: u32,
+        #[serde(default)]
+        member: Option<RoomMember>,
+    },
+    ClientLeft {
+        #[serde(alias = "clientId")]
+        client_id: String,
+        #[serde(alias = "clientCount")]
+        client_count: u32,
+    },
+    HostChanged {
+        #[serde(alias = "newHost")]
+        new_host: String,
+    },
+    // A host picked this registered worker; it is expected to join the room
+    RoomAssigned {
+        #[serde(alias = "roomId")]
+        room_id: String,
+    },
+    TestConfigured(TestConfig),
+    TestStarted(TestStartInfo),
+    TestAborted {
+        #[serde(alias = "clientId")]
+        client_id: String,
+        reason: String,
+    },
+    TestCompleted(AggregatedResult),
+    LiveMetrics(LiveMetrics),
+    // Raised locally, never sent by the coordinator
+    Disconnected,
+    Reconnected {
+        room_id: Option<String>,
+    },
+}
@@ -0,0 +1,337 @@
+use reqwest::StatusCode;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_native_tls::{native_tls, TlsConnector};
+use url::Url;
+
+// Upper bound on how much of a response is read before giving up on framing
+const MAX_RESPONSE_BYTES: usize = 16 * 1024 * 1024;
+
+// Fills the placeholders supported in raw request templates.
+pub fn render_template(template: &str, url: &Url) -> String {
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+
+    template.replace("{{host}}", &host).replace("{{path}}", &path)
+}
+
+// Sends the request bytes exactly as given on a fresh connection and returns
+// the status code from the response's status line, with when the response's
+// headers had arrived. The rest of the response is still read, so the
+// connection is left in a known state.
+pub async fn send(
+    url: &Url,
+    addr: Option<SocketAddr>,
+    request: &[u8],
+    timeout: Duration,
+) -> Result<(StatusCode, Instant), String> {
+    tokio::time::timeout(timeout, exchange(url, addr, request))
+        .await
+        .map_err(|_| "Raw request timed out".to_string())?
+}
+
+async fn exchange(url: &Url, addr: Option<SocketAddr>, request: &[u8]) -> Result<(StatusCode, Instant), String> {
+    let host = url.host_str().ok_or_else(|| "Invalid URL: missing host".to_string())?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| "Invalid URL: unknown port".to_string())?;
+
+    let stream = match addr {
+        Some(addr) => TcpStream::connect(SocketAddr::new(addr.ip(), port)).await,
+        None => TcpStream::connect((host, port)).await,
+    }
+    .map_err(|e| format!("Failed to connect: {}", e))?;
+
+    if url.scheme() == "https" {
+        let connector = native_tls::TlsConnector::new()
+            .map(TlsConnector::from)
+            .map_err(|e| format!("Failed to create TLS connector: {}", e))?;
+        let stream = connector
+            .connect(host, stream)
+            .await
+            .map_err(|e| format!("TLS handshake failed: {}", e))?;
+        round_trip(stream, request).await
+    } else {
+        round_trip(stream, request).await
+    }
+}
+
+async fn round_trip<S>(mut stream: S, request: &[u8]) -> Result<(StatusCode, Instant), String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream
+        .write_all(request)
+        .await
+        .map_err(|e| format!("Failed to write request: {}", e))?;
+    stream
+        .flush()
+        .await
+        .map_err(|e| format!("Failed to write request: {}", e))?;
+
+    // RFC 9112 §6.3: a response to HEAD has no body, whatever its headers say
+    let head_request = request.starts_with(b"HEAD ");
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let mut status = None;
+    let mut responded = None;
+
+    loop {
+        let read = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        response.extend_from_slice(&chunk[..read]);
+
+        let progress = read_progress(&response, head_request)?;
+        status = progress.status;
+        if progress.headers && responded.is_none() {
+            responded = Some(Instant::now());
+        }
+        if progress.complete || response.len() > MAX_RESPONSE_BYTES {
+            break;
+        }
+    }
+
+    let status = match status {
+        Some(status) => status,
+        None if response.is_empty() => return Err("Connection closed without a response".to_string()),
+        None => parse_status_line(&response)?,
+    };
+    Ok((status, responded.unwrap_or_else(Instant::now)))
+}
+
+fn parse_status_line(line: &[u8]) -> Result<StatusCode, String> {
+    let line = String::from_utf8_lossy(line);
+    let mut parts = line.split_whitespace();
+
+    match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/") => code
+            .parse::<u16>()
+            .ok()
+            .and_then(|code| StatusCode::from_u16(code).ok())
+            .ok_or_else(|| format!("Malformed response status line: {}", line.trim())),
+        _ => Err(format!("Malformed response status line: {}", line.trim())),
+    }
+}
+
+// How much of a response has been read
+#[derive(Debug, Default, PartialEq)]
+struct Progress {
+    // Set once a status line is in; an interim 1xx until the final one arrives
+    status: Option<StatusCode>,
+    // The final response's header section is complete
+    headers: bool,
+    complete: bool,
+}
+
+impl Progress {
+    fn new(status: StatusCode, headers: bool, complete: bool) -> Self {
+        Progress {
+            status: Some(status),
+            headers,
+            complete,
+        }
+    }
+}
+
+// Works out the progress of the response being read. Interim 1xx responses
+// are skipped. Without a length or chunked framing the server is expected to
+// close the connection.
+fn read_progress(response: &[u8], head_request: bool) -> Result<Progress, String> {
+    let mut rest = response;
+    let mut interim = None;
+    loop {
+        let status = match find(rest, b"\r\n") {
+            Some(line_end) => parse_status_line(&rest[..line_end])?,
+            None => {
+                return Ok(Progress {
+                    status: interim,
+                    ..Default::default()
+                })
+            }
+        };
+        let header_end = match find(rest, b"\r\n\r\n") {
+            Some(index) => index + 4,
+            None => return Ok(Progress::new(status, false, false)),
+        };
+        let body = &rest[header_end..];
+
+        // RFC 9112 §6.3: 1xx, 204 and 304 responses never have a body. After
+        // 101 the connection speaks another protocol, so that ends it too.
+        if status.is_informational() && status != StatusCode::SWITCHING_PROTOCOLS {
+            interim = Some(status);
+            rest = body;
+            continue;
+        }
+        if head_request
+            || status == StatusCode::SWITCHING_PROTOCOLS
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+        {
+            return Ok(Progress::new(status, true, true));
+        }
+
+        let headers = String::from_utf8_lossy(&rest[..header_end]).to_ascii_lowercase();
+        let mut content_length = None;
+        for line in headers.lines() {
+            // Transfer-Encoding takes precedence over Content-Length
+            if let Some(value) = line.strip_prefix("transfer-encoding:") {
+                if value.rsplit(',').next().is_some_and(|coding| coding.trim() == "chunked") {
+                    return Ok(Progress::new(status, true, chunked_complete(body)?));
+                }
+            }
+            if let Some(value) = line.strip_prefix("content-length:") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+        let complete = content_length.is_some_and(|length| body.len() >= length);
+        return Ok(Progress::new(status, true, complete));
+    }
+}
+
+// Walks the chunk sizes of a chunked body to see whether the last chunk and
+// the trailer section have arrived
+fn chunked_complete(mut body: &[u8]) -> Result<bool, String> {
+    loop {
+        let line_end = match find(body, b"\r\n") {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        let line = String::from_utf8_lossy(&body[..line_end]);
+        // Chunk extensions follow a semicolon
+        let size_field = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_field, 16)
+            .map_err(|_| format!("Malformed chunk size: {}", line.trim()))?;
+        body = &body[line_end + 2..];
+
+        if size == 0 {
+            // Trailer fields, if any, end with an empty line
+            return Ok(body.starts_with(b"\r\n") || find(body, b"\r\n\r\n").is_some());
+        }
+        if body.len() < size + 2 {
+            return Ok(false);
+        }
+        body = &body[size + 2..];
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(response: &str, head_request: bool) -> (Option<u16>, bool) {
+        let progress = read_progress(response.as_bytes(), head_request).unwrap();
+        (progress.status.map(|status| status.as_u16()), progress.complete)
+    }
+
+    fn headers_in(response: &str) -> bool {
+        read_progress(response.as_bytes(), false).unwrap().headers
+    }
+
+    #[test]
+    fn headers_are_in_before_the_body() {
+        assert!(!headers_in("HTTP/1.1 200 OK\r\nContent-Len"));
+        assert!(headers_in("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n"));
+        assert!(headers_in("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhe"));
+        // An interim response's headers don't count
+        assert!(!headers_in("HTTP/1.1 100 Continue\r\n\r\n"));
+        assert!(!headers_in("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n"));
+    }
+
+    #[tokio::test]
+    async fn latency_stops_at_the_headers() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            stream.write_all(b"ok").await.unwrap();
+        });
+
+        let sent = Instant::now();
+        let request = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let (status, responded) = send(&url, None, request, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert!(responded.duration_since(sent) < Duration::from_millis(250));
+        // The body was still read to the end
+        assert!(sent.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn content_length_body() {
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhel";
+        assert_eq!(progress(response, false), (Some(200), false));
+        assert_eq!(progress(&format!("{}lo", response), false), (Some(200), true));
+    }
+
+    #[test]
+    fn bodyless_statuses_ignore_content_length() {
+        for status in ["204 No Content", "304 Not Modified"] {
+            let response = format!("HTTP/1.1 {}\r\nContent-Length: 10\r\n\r\n", status);
+            assert!(progress(&response, false).1, "{}", status);
+        }
+    }
+
+    #[test]
+    fn head_response_has_no_body() {
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 1024\r\n\r\n";
+        assert_eq!(progress(response, true), (Some(200), true));
+        assert_eq!(progress(response, false), (Some(200), false));
+    }
+
+    #[test]
+    fn interim_responses_are_skipped() {
+        let interim = "HTTP/1.1 100 Continue\r\n\r\n";
+        assert_eq!(progress(interim, false), (Some(100), false));
+        let response = format!("{}HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nok", interim);
+        assert_eq!(progress(&response, false), (Some(201), true));
+    }
+
+    #[test]
+    fn chunked_body() {
+        let head = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let cases = [
+            ("5\r\nhello\r\n", false),
+            ("5\r\nhello\r\n0\r\n", false),
+            ("5\r\nhello\r\n0\r\n\r\n", true),
+            ("5;ext=1\r\nhello\r\n0\r\nTrailer: x\r\n", false),
+            ("5;ext=1\r\nhello\r\n0\r\nTrailer: x\r\n\r\n", true),
+            // Chunk data that happens to look like the last chunk
+            ("a\r\n0\r\n\r\n0\r\n\r\n", false),
+            ("a\r\n0\r\n\r\n0\r\n\r\n\r\n0\r\n\r\n", true),
+        ];
+        for (body, complete) in cases {
+            assert_eq!(progress(&format!("{}{}", head, body), false).1, complete, "{:?}", body);
+        }
+    }
+
+    #[test]
+    fn malformed_chunk_size_is_an_error() {
+        let response = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        assert!(read_progress(response.as_bytes(), false).is_err());
+    }
+
+    #[test]
+    fn unframed_body_waits_for_close() {
+        assert_eq!(progress("HTTP/1.1 200 OK\r\n\r\nbody", false), (Some(200), false));
+    }
+}
@@ -0,0 +1,446 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{oneshot, Mutex};
+use tokio::task;
+
+use crate::histogram::Histogram;
+use crate::stress_test::{MetricsDelta, TestResult};
+
+// Past this many intervals neighbours are merged in pairs, so a run of any
+// length keeps a fixed amount of history
+const MAX_INTERVALS: usize = 720;
+// How well a straight line must fit before a change counts as a trend rather
+// than noise (coefficient of determination)
+const MIN_TREND_FIT: f64 = 0.5;
+// Fewer intervals than this can't show a trend
+const MIN_TREND_INTERVALS: usize = 4;
+
+// Settings for long runs. The run is summarised every `interval_ms`, and the
+// summaries are checked for steady drift once it ends.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SoakConfig {
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+    // Appends one JSON line per interval to <snapshot_dir>/<test_id>.jsonl
+    #[serde(default)]
+    pub snapshot_dir: Option<String>,
+    // Flag p99 latency rising by this percentage over the run
+    #[serde(default = "default_latency_growth_pct")]
+    pub latency_growth_pct: f64,
+    // Flag throughput falling by this percentage over the run
+    #[serde(default = "default_throughput_drop_pct")]
+    pub throughput_drop_pct: f64,
+    // Flag the error rate climbing by this many percentage points
+    #[serde(default = "default_error_rate_rise")]
+    pub error_rate_rise: f64,
+}
+
+fn default_interval_ms() -> u64 {
+    60_000
+}
+
+fn default_latency_growth_pct() -> f64 {
+    25.0
+}
+
+fn default_throughput_drop_pct() -> f64 {
+    20.0
+}
+
+fn default_error_rate_rise() -> f64 {
+    1.0
+}
+
+// One interval's figures; times are milliseconds since the run started
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SoakInterval {
+    pub start: f64,
+    pub end: f64,
+    pub requests: u32,
+    pub failed_requests: u32,
+    pub total_response_time: f64,
+    pub avg_response_time: f64,
+    pub p50_response_time: f64,
+    pub p99_response_time: f64,
+    pub throughput: f64,
+    pub error_rate: f64,
+    // Kept so merged intervals get true percentiles; left out of reports and
+    // snapshots to keep them small
+    #[serde(skip)]
+    pub latency_histogram: Histogram,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SoakTrend {
+    // "p99_response_time", "throughput" or "error_rate"
+    pub metric: String,
+    // Fitted values at the start and end of the run
+    pub from: f64,
+    pub to: f64,
+    pub unit: String,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SoakReport {
+    pub intervals: Vec<SoakInterval>,
+    pub trends: Vec<SoakTrend>,
+    #[serde(default)]
+    pub snapshot_file: Option<String>,
+}
+
+// A line of the snapshot file: the latest interval and the run so far
+#[derive(Debug, Serialize)]
+struct Snapshot<'a> {
+    elapsed: f64,
+    interval: &'a SoakInterval,
+    trends: &'a [SoakTrend],
+    result: &'a TestResult,
+}
+
+impl SoakInterval {
+    fn from_delta(start: f64, delta: &MetricsDelta) -> Self {
+        let mut interval = SoakInterval {
+            start,
+            end: delta.elapsed,
+            requests: delta.requests,
+            failed_requests: delta.failed_requests,
+            total_response_time: delta.total_response_time,
+            latency_histogram: delta.latency_histogram.clone(),
+            ..Default::default()
+        };
+        interval.derive();
+        interval
+    }
+
+    fn derive(&mut self) {
+        let seconds = (self.end - self.start) / 1000.0;
+        self.throughput = if seconds > 0.0 { self.requests as f64 / seconds } else { 0.0 };
+        if self.requests > 0 {
+            self.avg_response_time = self.total_response_time / self.requests as f64;
+            self.error_rate = self.failed_requests as f64 / self.requests as f64 * 100.0;
+        }
+        // Intervals read back from a report have no histogram
+        if self.latency_histogram.count() > 0 {
+            self.p50_response_time = self.latency_histogram.percentile(50.0);
+            self.p99_response_time = self.latency_histogram.percentile(99.0);
+        }
+    }
+
+    // Joins the next interval onto this one
+    fn absorb(&mut self, next: &SoakInterval) {
+        self.latency_histogram.merge(&next.latency_histogram);
+        self.end = next.end;
+        self.requests += next.requests;
+        self.failed_requests += next.failed_requests;
+        self.total_response_time += next.total_response_time;
+        self.derive();
+    }
+
+    // Adds another worker's figures for the same stretch of time. Without
+    // histograms, p50 and p99 take the slower worker's.
+    fn merge(&mut self, other: &SoakInterval) {
+        self.latency_histogram.merge(&other.latency_histogram);
+        self.p50_response_time = self.p50_response_time.max(other.p50_response_time);
+        self.p99_response_time = self.p99_response_time.max(other.p99_response_time);
+        self.start = self.start.min(other.start);
+        self.end = self.end.max(other.end);
+        self.requests += other.requests;
+        self.failed_requests += other.failed_requests;
+        self.total_response_time += other.total_response_time;
+        self.derive();
+    }
+}
+
+impl SoakReport {
+    fn push(&mut self, interval: SoakInterval) {
+        self.intervals.push(interval);
+        if self.intervals.len() > MAX_INTERVALS {
+            self.intervals = self
+                .intervals
+                .chunks(2)
+                .map(|pair| {
+                    let mut merged = pair[0].clone();
+                    if let Some(next) = pair.get(1) {
+                        merged.absorb(next);
+                    }
+                    merged
+                })
+                .collect();
+        }
+    }
+
+    // Combines workers' reports interval by interval; p50 and p99 take the
+    // slowest worker's. Each worker's trends are kept as they are.
+    pub fn merge(&mut self, other: &SoakReport) {
+        for (index, interval) in other.intervals.iter().enumerate() {
+            match self.intervals.get_mut(index) {
+                Some(mine) => mine.merge(interval),
+                None => self.intervals.push(interval.clone()),
+            }
+        }
+        self.trends.extend(other.trends.iter().cloned());
+        if self.snapshot_file.is_none() {
+            self.snapshot_file = other.snapshot_file.clone();
+        }
+    }
+}
+
+// Least-squares line through the points: (slope, intercept, r²)
+fn fit(points: &[(f64, f64)]) -> Option<(f64, f64, f64)> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let syy: f64 = points.iter().map(|(_, y)| (y - mean_y).powi(2)).sum();
+    let sxy: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    if sxx == 0.0 || syy == 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    Some((slope, mean_y - slope * mean_x, sxy * sxy / (sxx * syy)))
+}
+
+// Looks for steady drift across the intervals that carried traffic. The short
+// interval left over at the end is too noisy to count.
+pub fn analyze(intervals: &[SoakInterval], config: &SoakConfig) -> Vec<SoakTrend> {
+    let shortest = config.interval_ms as f64 / 2.0;
+    let active: Vec<&SoakInterval> = intervals
+        .iter()
+        .filter(|interval| interval.requests > 0 && interval.end - interval.start >= shortest)
+        .collect();
+    if active.len() < MIN_TREND_INTERVALS {
+        return Vec::new();
+    }
+    let (first, last) = (active[0], active[active.len() - 1]);
+    let span = format_span(last.end - first.start);
+
+    // Fitted values at the first and last interval
+    let line = |metric: fn(&SoakInterval) -> f64| {
+        let points: Vec<(f64, f64)> = active
+            .iter()
+            .map(|interval| ((interval.start + interval.end) / 2.0, metric(interval)))
+            .collect();
+        fit(&points).filter(|(_, _, r2)| *r2 >= MIN_TREND_FIT).map(|(slope, intercept, _)| {
+            let at = |point: &(f64, f64)| (intercept + slope * point.0).max(0.0);
+            (at(&points[0]), at(&points[points.len() - 1]))
+        })
+    };
+
+    let mut trends = Vec::new();
+    if let Some((from, to)) = line(|interval| interval.p99_response_time) {
+        if from > 0.0 && (to - from) / from * 100.0 >= config.latency_growth_pct {
+            trends.push(SoakTrend {
+                metric: "p99_response_time".to_string(),
+                from,
+                to,
+                unit: "ms".to_string(),
+                description: format!("p99 latency rose from {:.1} ms to {:.1} ms over {}", from, to, span),
+            });
+        }
+    }
+    if let Some((from, to)) = line(|interval| interval.throughput) {
+        if from > 0.0 && (from - to) / from * 100.0 >= config.throughput_drop_pct {
+            trends.push(SoakTrend {
+                metric: "throughput".to_string(),
+                from,
+                to,
+                unit: "req/s".to_string(),
+                description: format!("Throughput fell from {:.1} to {:.1} req/s over {}", from, to, span),
+            });
+        }
+    }
+    if let Some((from, to)) = line(|interval| interval.error_rate) {
+        if to - from >= config.error_rate_rise {
+            trends.push(SoakTrend {
+                metric: "error_rate".to_string(),
+                from,
+                to,
+                unit: "%".to_string(),
+                description: format!("Error rate climbed from {:.2}% to {:.2}% over {}", from, to, span),
+            });
+        }
+    }
+    trends
+}
+
+fn format_span(ms: f64) -> String {
+    let minutes = ms / 60_000.0;
+    if minutes >= 90.0 {
+        format!("{:.1} h", minutes / 60.0)
+    } else if minutes >= 1.0 {
+        format!("{:.0} min", minutes)
+    } else {
+        format!("{:.0} s", ms / 1000.0)
+    }
+}
+
+async fn open_snapshot_file(dir: &str, test_id: &str) -> Result<(PathBuf, tokio::fs::File), String> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| format!("Failed to create snapshot directory {}: {}", dir, e))?;
+    let path = PathBuf::from(dir).join(format!("{}.jsonl", test_id));
+    let file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+        .map_err(|e| format!("Failed to open snapshot file {}: {}", path.display(), e))?;
+    Ok((path, file))
+}
+
+// Summarises a running test every interval until finished
+pub struct SoakMonitor {
+    stop: oneshot::Sender<()>,
+    handle: task::JoinHandle<SoakReport>,
+}
+
+impl SoakMonitor {
+    // The snapshot file is opened before the run so a bad path fails it early
+    pub async fn start(
+        config: SoakConfig,
+        results: Arc<Mutex<TestResult>>,
+        start_time: Instant,
+        test_id: &str,
+    ) -> Result<Self, String> {
+        if config.interval_ms == 0 {
+            return Err("Soak interval_ms must be above zero".to_string());
+        }
+        let mut snapshots = match &config.snapshot_dir {
+            Some(dir) => Some(open_snapshot_file(dir, test_id).await?),
+            None => None,
+        };
+
+        let (stop, mut stopped) = oneshot::channel();
+        let handle = task::spawn(async move {
+            let mut report = SoakReport {
+                snapshot_file: snapshots.as_ref().map(|(path, _)| path.display().to_string()),
+                ..Default::default()
+            };
+            let mut previous = TestResult::default();
+            let mut ticker = tokio::time::interval(Duration::from_millis(config.interval_ms));
+            ticker.tick().await;
+
+            loop {
+                let finished = tokio::select! {
+                    _ = ticker.tick() => false,
+                    _ = &mut stopped => true,
+                };
+
+                let mut current = results.lock().await.clone();
+                let elapsed = start_time.elapsed();
+                let delta = MetricsDelta::between(&previous, &current, elapsed);
+                let interval = SoakInterval::from_delta(report.intervals.last().map_or(0.0, |last| last.end), &delta);
+                previous = current.clone();
+                report.push(interval.clone());
+
+                if let Some((path, file)) = snapshots.as_mut() {
+                    current.finalize(elapsed.as_secs_f64() * 1000.0);
+                    let trends = analyze(&report.intervals, &config);
+                    let snapshot = Snapshot {
+                        elapsed: delta.elapsed,
+                        interval: &interval,
+                        trends: &trends,
+                        result: &current,
+                    };
+                    let written = match serde_json::to_vec(&snapshot) {
+                        Ok(mut line) => {
+                            line.push(b'\n');
+                            file.write_all(&line).await.map_err(|e| e.to_string())
+                        }
+                        Err(e) => Err(e.to_string()),
+                    };
+                    if let Err(e) = written {
+                        log::warn!("Failed to write soak snapshot to {}: {}", path.display(), e);
+                    }
+                }
+
+                if finished {
+                    break;
+                }
+            }
+
+            report.trends = analyze(&report.intervals, &config);
+            report
+        });
+
+        Ok(SoakMonitor { stop, handle })
+    }
+
+    // Records the last, partial interval and returns the report
+    pub async fn finish(self) -> Result<SoakReport, String> {
+        let _ = self.stop.send(());
+        self.handle.await.map_err(|e| format!("Soak monitor failed: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: f64 = 60_000.0;
+
+    fn config() -> SoakConfig {
+        serde_json::from_str("{}").unwrap()
+    }
+
+    // A minute of traffic at the given latencies, as (ms, requests) pairs
+    fn interval(index: usize, latencies: &[(f64, u64)]) -> SoakInterval {
+        let mut interval = SoakInterval {
+            start: index as f64 * MINUTE,
+            end: (index + 1) as f64 * MINUTE,
+            ..Default::default()
+        };
+        for &(latency, requests) in latencies {
+            interval.latency_histogram.record_n(latency, requests);
+            interval.requests += requests as u32;
+            interval.total_response_time += latency * requests as f64;
+        }
+        interval.derive();
+        interval
+    }
+
+    #[test]
+    fn absorbed_intervals_recompute_p99() {
+        let mut steady = interval(0, &[(10.0, 1000)]);
+        steady.absorb(&interval(1, &[(10.0, 98), (500.0, 2)]));
+
+        // Two slow requests in 1100 are under the 1% tail
+        assert_eq!(steady.requests, 1100);
+        assert!((steady.p99_response_time - 10.0).abs() < 0.5, "{}", steady.p99_response_time);
+        assert_eq!(steady.end, 2.0 * MINUTE);
+    }
+
+    #[test]
+    fn rising_p99_is_found_past_the_interval_cap() {
+        let total = 3 * MAX_INTERVALS;
+        let mut report = SoakReport::default();
+        for index in 0..total {
+            // p99 climbs steadily from 100 ms to 200 ms while the median holds
+            let slow = 100.0 + 100.0 * index as f64 / total as f64;
+            report.push(interval(index, &[(20.0, 980), (slow, 20)]));
+        }
+        assert!(report.intervals.len() <= MAX_INTERVALS);
+        assert_eq!(report.intervals.last().unwrap().end, total as f64 * MINUTE);
+
+        let trends = analyze(&report.intervals, &config());
+        assert_eq!(trends.len(), 1, "{:?}", trends);
+        let trend = &trends[0];
+        assert_eq!(trend.metric, "p99_response_time");
+        assert!((trend.from - 100.0).abs() < 10.0, "{:?}", trend);
+        assert!((trend.to - 200.0).abs() < 15.0, "{:?}", trend);
+    }
+
+    #[test]
+    fn steady_runs_show_no_trend() {
+        let mut report = SoakReport::default();
+        for index in 0..2 * MAX_INTERVALS {
+            let jitter = if index % 2 == 0 { 95.0 } else { 105.0 };
+            report.push(interval(index, &[(20.0, 980), (jitter, 20)]));
+        }
+        assert!(analyze(&report.intervals, &config()).is_empty());
+    }
+}
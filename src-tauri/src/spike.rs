@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::stress_test::TestResult;
+
+// Flash-crowd traffic on top of a rate-limited run. The rate jumps to
+// `multiplier` times rate_per_second for `burst_ms` at each burst start, then
+// drops back. Starts are milliseconds from the start of the run, warm-up
+// included, and may be listed in `at_ms`, repeat every `every_ms`, or both.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SpikeProfile {
+    pub multiplier: f64,
+    pub burst_ms: u64,
+    #[serde(default)]
+    pub at_ms: Vec<u64>,
+    // The first periodic burst starts after one period of baseline load
+    #[serde(default)]
+    pub every_ms: Option<u64>,
+    // How long after a burst ends its requests count as recovery; defaults to
+    // burst_ms
+    #[serde(default)]
+    pub recovery_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SpikePhase {
+    Baseline,
+    Burst,
+    Recovery,
+}
+
+// Burst and recovery figures of a spike run. Their requests also count in
+// the run's overall figures.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SpikeReport {
+    // Bursts that fell in the measured part of the run
+    pub bursts: u32,
+    pub burst: TestResult,
+    pub recovery: TestResult,
+}
+
+impl SpikeReport {
+    // Callers finalize the merged burst and recovery with their own durations
+    pub fn merge(&mut self, other: &SpikeReport) {
+        self.bursts = self.bursts.max(other.bursts);
+        self.burst.merge(&other.burst);
+        self.recovery.merge(&other.recovery);
+    }
+}
+
+impl SpikeProfile {
+    pub(crate) fn validate(&self, rate_per_second: Option<f64>) -> Result<(), String> {
+        if !rate_per_second.is_some_and(|rate| rate > 0.0) {
+            return Err("A spike profile needs rate_per_second as its baseline".to_string());
+        }
+        if !self.multiplier.is_finite() || self.multiplier <= 0.0 {
+            return Err("Spike multiplier must be above zero".to_string());
+        }
+        if self.burst_ms == 0 || self.every_ms == Some(0) {
+            return Err("Spike burst_ms and every_ms must be above zero".to_string());
+        }
+        if self.at_ms.is_empty() && self.every_ms.is_none() {
+            return Err("A spike profile needs at_ms, every_ms or both".to_string());
+        }
+        Ok(())
+    }
+
+    fn recovery(&self) -> u64 {
+        self.recovery_ms.unwrap_or(self.burst_ms)
+    }
+
+    // The latest burst start at or before `offset_ms`. A burst and its
+    // recovery last at most until the next burst starts.
+    fn last_start(&self, offset_ms: u64) -> Option<u64> {
+        let listed = self.at_ms.iter().copied().filter(|start| *start <= offset_ms).max();
+        let periodic = self
+            .every_ms
+            .map(|every| offset_ms / every * every)
+            .filter(|start| *start > 0);
+        listed.max(periodic)
+    }
+
+    fn phase(&self, offset: Duration) -> SpikePhase {
+        let offset_ms = offset.as_millis() as u64;
+        match self.last_start(offset_ms).map(|start| offset_ms - start) {
+            Some(since) if since < self.burst_ms => SpikePhase::Burst,
+            Some(since) if since < self.burst_ms + self.recovery() => SpikePhase::Recovery,
+            _ => SpikePhase::Baseline,
+        }
+    }
+
+    // The first burst start after `offset`
+    fn next_start(&self, offset: Duration) -> Option<Duration> {
+        let offset_ms = offset.as_millis() as u64;
+        let listed = self.at_ms.iter().copied().filter(|start| *start > offset_ms).min();
+        let periodic = self.every_ms.map(|every| (offset_ms / every + 1) * every);
+        listed.into_iter().chain(periodic).min().map(Duration::from_millis)
+    }
+
+    // When the request after one due at `offset` is due: an interval at the
+    // rate in force then, or sooner if a burst starts in between
+    pub(crate) fn next_send(&self, rate: f64, offset: Duration) -> Duration {
+        let rate = match self.phase(offset) {
+            SpikePhase::Burst => rate * self.multiplier,
+            _ => rate,
+        };
+        let next = offset + Duration::from_secs_f64(1.0 / rate);
+        self.next_start(offset).filter(|start| *start < next).unwrap_or(next)
+    }
+
+    // Bursts that overlap [from_ms, to_ms), and the milliseconds of that span
+    // spent in bursts and in recovery
+    fn coverage(&self, from_ms: f64, to_ms: f64) -> (u32, f64, f64) {
+        let mut starts: Vec<f64> = self.at_ms.iter().map(|start| *start as f64).collect();
+        if let Some(every) = self.every_ms {
+            starts.extend((1..).map(|n| (n * every) as f64).take_while(|start| *start < to_ms));
+        }
+        starts.retain(|start| *start < to_ms);
+        starts.sort_by(f64::total_cmp);
+        starts.dedup();
+
+        let overlap = |start: f64, end: f64| (end.min(to_ms) - start.max(from_ms)).max(0.0);
+        let (mut bursts, mut burst_ms, mut recovery_ms) = (0, 0.0, 0.0);
+        for (i, start) in starts.iter().enumerate() {
+            let next = starts.get(i + 1).copied().unwrap_or(f64::INFINITY);
+            let burst_end = (start + self.burst_ms as f64).min(next);
+            let recovery_end = (burst_end + self.recovery() as f64).min(next);
+            let in_burst = overlap(*start, burst_end);
+            if in_burst > 0.0 {
+                bursts += 1;
+            }
+            burst_ms += in_burst;
+            recovery_ms += overlap(burst_end, recovery_end);
+        }
+        (bursts, burst_ms, recovery_ms)
+    }
+}
+
+// Collects the requests of each burst and recovery window as a run goes
+pub(crate) struct SpikeRecorder {
+    profile: SpikeProfile,
+    start: Instant,
+    burst: Arc<Mutex<TestResult>>,
+    recovery: Arc<Mutex<TestResult>>,
+}
+
+impl SpikeRecorder {
+    pub(crate) fn new(profile: SpikeProfile, start: Instant, template: &TestResult) -> Self {
+        SpikeRecorder {
+            profile,
+            start,
+            burst: Arc::new(Mutex::new(template.clone())),
+            recovery: Arc::new(Mutex::new(template.clone())),
+        }
+    }
+
+    // Where a request scheduled for `slot` belongs, if anywhere besides the
+    // overall figures
+    pub(crate) fn results_for(&self, slot: Instant) -> Option<&Arc<Mutex<TestResult>>> {
+        match self.profile.phase(slot.saturating_duration_since(self.start)) {
+            SpikePhase::Burst => Some(&self.burst),
+            SpikePhase::Recovery => Some(&self.recovery),
+            SpikePhase::Baseline => None,
+        }
+    }
+
+    // Covers the measured part of the run, from `measured_from` to `end`
+    pub(crate) async fn finish(&self, measured_from: Instant, end: Instant) -> SpikeReport {
+        let ms = |at: Instant| at.duration_since(self.start).as_secs_f64() * 1000.0;
+        let (bursts, burst_ms, recovery_ms) = self.profile.coverage(ms(measured_from), ms(end));
+        let mut burst = self.burst.lock().await.clone();
+        burst.finalize(burst_ms);
+        let mut recovery = self.recovery.lock().await.clone();
+        recovery.finalize(recovery_ms);
+        SpikeReport {
+            bursts,
+            burst,
+            recovery,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(at_ms: &[u64], every_ms: Option<u64>) -> SpikeProfile {
+        SpikeProfile {
+            multiplier: 4.0,
+            burst_ms: 100,
+            at_ms: at_ms.to_vec(),
+            every_ms,
+            recovery_ms: Some(200),
+        }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn bursts_give_way_to_recovery_then_baseline() {
+        use SpikePhase::*;
+        let profile = profile(&[250], None);
+        let phases: Vec<_> = [0, 249, 250, 349, 350, 549, 550].iter().map(|at| profile.phase(ms(*at))).collect();
+        assert_eq!(phases, [Baseline, Baseline, Burst, Burst, Recovery, Recovery, Baseline]);
+    }
+
+    #[test]
+    fn an_overlapping_burst_cuts_the_previous_one_short() {
+        use SpikePhase::*;
+        let profile = profile(&[950], Some(1000));
+        // No periodic burst at the very start of the run
+        assert_eq!(profile.phase(ms(0)), Baseline);
+        assert_eq!(profile.phase(ms(950)), Burst);
+        // The periodic burst at 1000 starts over, so 1049 is still a burst
+        assert_eq!(profile.phase(ms(1049)), Burst);
+        assert_eq!(profile.phase(ms(1100)), Recovery);
+        assert_eq!(profile.phase(ms(1300)), Baseline);
+        assert_eq!(profile.phase(ms(2000)), Burst);
+    }
+
+    #[test]
+    fn bursts_speed_up_sends_and_pull_in_the_first_one() {
+        let profile = profile(&[250], Some(1000));
+        assert_eq!(profile.next_send(10.0, ms(0)), ms(100));
+        // The burst at 250 comes before the next baseline send at 300
+        assert_eq!(profile.next_send(10.0, ms(200)), ms(250));
+        assert_eq!(profile.next_send(10.0, ms(250)), ms(275));
+        assert_eq!(profile.next_send(10.0, ms(325)), ms(350));
+        assert_eq!(profile.next_send(10.0, ms(350)), ms(450));
+        assert_eq!(profile.next_send(10.0, ms(950)), ms(1000));
+    }
+
+    #[test]
+    fn coverage_counts_bursts_within_the_span() {
+        let profile = profile(&[250], Some(1000));
+        assert_eq!(profile.coverage(0.0, 2000.0), (2, 200.0, 400.0));
+        // Partly measured bursts count, with only their measured time
+        assert_eq!(profile.coverage(300.0, 1050.0), (2, 100.0, 200.0));
+        // A burst over before the span still leaves recovery in it, and one
+        // starting at its end isn't in it
+        assert_eq!(profile.coverage(350.0, 1000.0), (0, 0.0, 200.0));
+        assert_eq!(profile.coverage(0.0, 0.0), (0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn coverage_of_overlapping_bursts_counts_each_once() {
+        assert_eq!(profile(&[950], Some(1000)).coverage(0.0, 2000.0), (2, 150.0, 200.0));
+        assert_eq!(profile(&[1000], Some(1000)).coverage(0.0, 2000.0), (1, 100.0, 200.0));
+    }
+}
@@ -1,13 +1,101 @@
-use reqwest::{Client, Method};
+use base64::Engine;
+use bytes::Bytes;
+use reqwest::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, LOCATION};
+use reqwest::multipart::{Form, Part};
+use reqwest::{redirect, Body, Client, Method, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, watch, Mutex};
 use tokio::task;
 use url::Url;
 use uuid::Uuid;
 
+use crate::compression;
+use crate::histogram::Histogram;
+use crate::raw_http;
+use crate::soak::{SoakConfig, SoakMonitor, SoakReport};
+use crate::spike::{SpikeProfile, SpikeRecorder, SpikeReport};
+use crate::thresholds::{self, Verdict};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+// Smallest expected_interval_ms accepted for coordinated omission correction
+const MIN_EXPECTED_INTERVAL_MS: f64 = 1.0;
+
+// Hands out send times on one schedule shared by every virtual user, so the
+// combined rate holds however many of them there are
+struct Pacer {
+    start: Instant,
+    rate: f64,
+    issued: AtomicU64,
+    // With a spike profile the interval changes along the run, so the next
+    // send time is kept rather than worked out from the count
+    spike: Option<(SpikeProfile, std::sync::Mutex<Duration>)>,
+}
+
+impl Pacer {
+    fn next_slot(&self) -> Instant {
+        if let Some((profile, next)) = &self.spike {
+            let mut next = next.lock().unwrap_or_else(|e| e.into_inner());
+            let slot = *next;
+            *next = profile.next_send(self.rate, slot);
+            return self.start + slot;
+        }
+        let slot = self.issued.fetch_add(1, Ordering::Relaxed);
+        self.start + Duration::from_secs_f64(slot as f64 / self.rate)
+    }
+}
+
+// Decides which requests belong to the warm-up, shared by every virtual user
+// so a request-count warm-up adds up across them
+struct WarmupGate {
+    until: Option<Instant>,
+    requests: u64,
+    claimed: AtomicU64,
+    // When the first measured request was sent
+    measured_from: OnceLock<Instant>,
+}
+
+impl WarmupGate {
+    fn new(warmup: Option<&Warmup>, start: Instant) -> Self {
+        let (until, requests) = match warmup {
+            Some(Warmup::Duration { ms }) => (Some(start + Duration::from_millis(*ms)), 0),
+            Some(Warmup::Requests { count }) => (None, *count as u64),
+            None => (None, 0),
+        };
+        let gate = WarmupGate {
+            until,
+            requests,
+            claimed: AtomicU64::new(0),
+            measured_from: OnceLock::new(),
+        };
+        if requests == 0 {
+            let _ = gate.measured_from.set(until.unwrap_or(start));
+        }
+        gate
+    }
+
+    // True when a request sent now is part of the warm-up
+    fn claim(&self, now: Instant) -> bool {
+        if self.until.is_some_and(|until| now < until) {
+            return true;
+        }
+        if self.requests > 0 && self.claimed.fetch_add(1, Ordering::Relaxed) < self.requests {
+            return true;
+        }
+        self.measured_from.get_or_init(|| now);
+        false
+    }
+
+    // Measured runs last `duration`, counted from the end of the warm-up
+    fn deadline(&self, duration: Duration) -> Option<Instant> {
+        self.measured_from.get().map(|from| *from + duration)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TestConfig {
     pub url: String,
@@ -17,6 +105,247 @@ pub struct TestConfig {
     pub requests_per_client: u32,
     pub concurrency: u32,
     pub timeout_ms: u32,
+    // Host to IP overrides, like curl's --resolve. Requests to a host with
+    // several addresses are spread across them round-robin.
+    #[serde(default)]
+    pub resolve: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub redirect_policy: RedirectPolicy,
+    // Alternative to `body` for file, binary, multipart and form payloads
+    #[serde(default)]
+    pub body_source: Option<RequestBody>,
+    // Literal HTTP/1.1 request written to the socket instead of going through
+    // the HTTP client. `{{host}}` and `{{path}}` are filled in from `url`.
+    #[serde(default)]
+    pub raw_request: Option<String>,
+    // Encodings advertised in Accept-Encoding, e.g. ["gzip", "br", "zstd"]
+    #[serde(default)]
+    pub accept_encoding: Vec<String>,
+    // Gzip the request body once up front and send it with Content-Encoding
+    #[serde(default)]
+    pub compress_body: bool,
+    // Run until this much time has passed instead of stopping after
+    // requests_per_client requests
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    // Combined request rate of all virtual users. Requests are sent on a fixed
+    // schedule rather than back to back.
+    #[serde(default)]
+    pub rate_per_second: Option<f64>,
+    // Total load for a distributed run. The coordinator splits it between the
+    // workers in the room; a local run ignores it.
+    #[serde(default)]
+    pub load_target: Option<LoadTarget>,
+    // Pass/fail criteria such as "p95 < 300ms", checked once the run is over
+    #[serde(default)]
+    pub thresholds: Vec<String>,
+    // Stop early when the target is clearly falling over
+    #[serde(default)]
+    pub abort_conditions: Option<AbortConditions>,
+    // Load sent before measuring starts. It is reported in TestResult::warmup
+    // and doesn't count towards requests_per_client or duration_ms.
+    #[serde(default)]
+    pub warmup: Option<Warmup>,
+    // Pause each virtual user takes after a response before its next request
+    #[serde(default)]
+    pub think_time: Option<ThinkTime>,
+    // Minimum time from one request of a virtual user to its next, think
+    // time included, so concurrency maps onto a realistic user count
+    #[serde(default)]
+    pub pacing_ms: Option<u64>,
+    // How often a closed-loop user means to send. Slower responses are
+    // backfilled with the samples the requests it held up would have had, so
+    // a stall shows in the tail. Rate-limited runs measure from each request's
+    // scheduled send time instead and ignore this.
+    #[serde(default)]
+    pub expected_interval_ms: Option<f64>,
+    // Interval summaries, snapshots and drift detection for long runs
+    #[serde(default)]
+    pub soak: Option<SoakConfig>,
+    // Bursts above rate_per_second at set times or periodically
+    #[serde(default)]
+    pub spike: Option<SpikeProfile>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Warmup {
+    // Everything sent in the first `ms` milliseconds
+    Duration { ms: u64 },
+    // The first `count` requests from this client
+    Requests { count: u32 },
+}
+
+// Checked about once a second against the requests completed in the trailing
+// window. Nothing is judged until the test has run for a full window.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AbortConditions {
+    #[serde(default = "default_abort_window_ms")]
+    pub window_ms: u64,
+    // Percentage of failed requests in the window
+    #[serde(default)]
+    pub max_error_rate: Option<f64>,
+    #[serde(default)]
+    pub max_p99_ms: Option<f64>,
+    // Windows with fewer requests than this are too small to judge
+    #[serde(default = "default_abort_min_requests")]
+    pub min_requests: u32,
+}
+
+fn default_abort_window_ms() -> u64 {
+    10_000
+}
+
+fn default_abort_min_requests() -> u32 {
+    10
+}
+
+// Stops a running test early. Clones share the signal and the first reason
+// given wins.
+#[derive(Debug, Clone)]
+pub struct AbortSignal {
+    tx: Arc<watch::Sender<Option<String>>>,
+}
+
+impl Default for AbortSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AbortSignal {
+    pub fn new() -> Self {
+        let (tx, _) = watch::channel(None);
+        AbortSignal { tx: Arc::new(tx) }
+    }
+
+    pub fn abort(&self, reason: impl Into<String>) {
+        let reason = reason.into();
+        self.tx.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(reason);
+            true
+        });
+    }
+
+    pub fn reason(&self) -> Option<String> {
+        self.tx.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Option<String>> {
+        self.tx.subscribe()
+    }
+}
+
+// Drawn afresh for every request; all times in milliseconds
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ThinkTime {
+    Fixed { ms: f64 },
+    Uniform { min_ms: f64, max_ms: f64 },
+    // Samples below zero are clamped to no pause
+    Normal { mean_ms: f64, std_dev_ms: f64 },
+    Exponential { mean_ms: f64 },
+}
+
+impl ThinkTime {
+    fn validate(&self) -> Result<(), String> {
+        let values = match self {
+            ThinkTime::Fixed { ms } => vec![*ms],
+            ThinkTime::Uniform { min_ms, max_ms } => {
+                if min_ms > max_ms {
+                    return Err("Think time min_ms must not exceed max_ms".to_string());
+                }
+                vec![*min_ms, *max_ms]
+            }
+            ThinkTime::Normal { mean_ms, std_dev_ms } => vec![*mean_ms, *std_dev_ms],
+            ThinkTime::Exponential { mean_ms } => vec![*mean_ms],
+        };
+        if values.iter().all(|value| value.is_finite() && *value >= 0.0) {
+            Ok(())
+        } else {
+            Err("Think times must be zero or more milliseconds".to_string())
+        }
+    }
+
+    fn sample(&self) -> Duration {
+        let ms = match self {
+            ThinkTime::Fixed { ms } => *ms,
+            ThinkTime::Uniform { min_ms, max_ms } => min_ms + (max_ms - min_ms) * rand::random::<f64>(),
+            ThinkTime::Normal { mean_ms, std_dev_ms } => {
+                // Box-Muller; 1 - u keeps the logarithm finite
+                let (u1, u2) = (1.0 - rand::random::<f64>(), rand::random::<f64>());
+                mean_ms + std_dev_ms * (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+            }
+            ThinkTime::Exponential { mean_ms } => -mean_ms * (1.0 - rand::random::<f64>()).ln(),
+        };
+        Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LoadTarget {
+    Requests { total: u32 },
+    Rps { total: f64 },
+    VirtualUsers { total: u32 },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RequestBody {
+    File {
+        path: String,
+        content_type: Option<String>,
+    },
+    Base64 {
+        data: String,
+        content_type: Option<String>,
+    },
+    Multipart {
+        parts: Vec<MultipartPart>,
+    },
+    Form {
+        fields: Vec<(String, String)>,
+    },
+}
+
+// A multipart field carries either an inline `value` or the contents of `file`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MultipartPart {
+    pub name: String,
+    pub value: Option<String>,
+    pub file: Option<String>,
+    pub file_name: Option<String>,
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum RedirectPolicy {
+    None,
+    Limited { max_redirects: usize },
+    SameHost { max_redirects: usize },
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        // Matches reqwest's own default policy
+        RedirectPolicy::Limited { max_redirects: 10 }
+    }
+}
+
+impl RedirectPolicy {
+    fn max_redirects(&self) -> usize {
+        match self {
+            RedirectPolicy::None => 0,
+            RedirectPolicy::Limited { max_redirects } | RedirectPolicy::SameHost { max_redirects } => {
+                *max_redirects
+            }
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -33,6 +362,301 @@ pub struct TestResult {
     pub throughput: f64,
     pub test_id: String,
     pub timestamp: u64,
+    #[serde(default)]
+    pub resolved_addresses: HashMap<String, AddressStats>,
+    // Number of requests keyed by how many redirects they followed
+    #[serde(default)]
+    pub redirect_counts: HashMap<String, u32>,
+    // Status codes seen at each redirect hop; index 0 is the first response
+    #[serde(default)]
+    pub redirect_hops: Vec<HashMap<String, u32>>,
+    // Redirect chains cut short by the policy, keyed by reason
+    #[serde(default)]
+    pub redirects_stopped: HashMap<String, u32>,
+    // Responses keyed by Content-Encoding, "identity" when uncompressed
+    #[serde(default)]
+    pub content_encodings: HashMap<String, u32>,
+    #[serde(default)]
+    pub compressed_bytes: u64,
+    #[serde(default)]
+    pub decompressed_bytes: u64,
+    #[serde(default)]
+    pub decompression_time: f64,
+    #[serde(default)]
+    pub decompression_errors: u32,
+    // Size of one request body before and after compression
+    #[serde(default)]
+    pub request_body_bytes: u64,
+    #[serde(default)]
+    pub request_body_compressed_bytes: u64,
+    // Set by distributed workers: estimated offset of this machine's clock
+    // from the coordinator's, and the uncertainty of that estimate
+    #[serde(default)]
+    pub clock_offset: Option<f64>,
+    #[serde(default)]
+    pub clock_uncertainty: Option<f64>,
+    #[serde(default)]
+    pub p50_response_time: f64,
+    #[serde(default)]
+    pub p90_response_time: f64,
+    #[serde(default)]
+    pub p95_response_time: f64,
+    #[serde(default)]
+    pub p99_response_time: f64,
+    // Response times of completed requests, for percentiles and merging
+    #[serde(default)]
+    pub latency_histogram: Histogram,
+    // Set when the config had thresholds
+    #[serde(default)]
+    pub verdict: Option<Verdict>,
+    // Why the run was stopped early, if it was
+    #[serde(default)]
+    pub aborted: Option<String>,
+    // Requests sent during the warm-up, kept out of every figure above
+    #[serde(default)]
+    pub warmup: Option<Box<TestResult>>,
+    // Latency corrected for coordinated omission, alongside the measured
+    // figures above; set for rate-limited runs and expected_interval_ms
+    #[serde(default)]
+    pub corrected: Option<CorrectedLatency>,
+    // Per-interval figures and drift found in a soak run
+    #[serde(default)]
+    pub soak: Option<SoakReport>,
+    // Figures for the burst and recovery windows of a spike run
+    #[serde(default)]
+    pub spike: Option<Box<SpikeReport>>,
+}
+
+// The percentiles are derived from the histogram, so a coordinator may leave
+// them out
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct CorrectedLatency {
+    pub max_response_time: f64,
+    pub p50_response_time: f64,
+    pub p90_response_time: f64,
+    pub p95_response_time: f64,
+    pub p99_response_time: f64,
+    pub latency_histogram: Histogram,
+}
+
+impl CorrectedLatency {
+    fn record(&mut self, value_ms: f64, expected_interval_ms: Option<f64>) {
+        self.max_response_time = self.max_response_time.max(value_ms);
+        match expected_interval_ms {
+            Some(interval_ms) => self.latency_histogram.record_corrected(value_ms, interval_ms),
+            None => self.latency_histogram.record(value_ms),
+        }
+    }
+}
+
+impl TestResult {
+    // Works out the derived figures once the counters are complete
+    pub fn finalize(&mut self, duration: f64) {
+        self.duration = duration;
+
+        if self.total_requests > 0 {
+            self.avg_response_time = self.total_response_time / self.total_requests as f64;
+            if duration > 0.0 {
+                self.throughput = (self.total_requests as f64 / duration) * 1000.0; // requests per second
+            }
+        }
+
+        // Handle edge case where no requests were successful
+        if self.min_response_time == f64::MAX {
+            self.min_response_time = 0.0;
+        }
+
+        self.fill_percentiles();
+
+        for stats in self.resolved_addresses.values_mut() {
+            if stats.total_requests > 0 {
+                stats.avg_response_time = stats.total_response_time / stats.total_requests as f64;
+            }
+            if stats.min_response_time == f64::MAX {
+                stats.min_response_time = 0.0;
+            }
+        }
+    }
+
+    pub fn fill_percentiles(&mut self) {
+        self.p50_response_time = self.latency_histogram.percentile(50.0);
+        self.p90_response_time = self.latency_histogram.percentile(90.0);
+        self.p95_response_time = self.latency_histogram.percentile(95.0);
+        self.p99_response_time = self.latency_histogram.percentile(99.0);
+
+        if let Some(corrected) = self.corrected.as_mut() {
+            corrected.p50_response_time = corrected.latency_histogram.percentile(50.0);
+            corrected.p90_response_time = corrected.latency_histogram.percentile(90.0);
+            corrected.p95_response_time = corrected.latency_histogram.percentile(95.0);
+            corrected.p99_response_time = corrected.latency_histogram.percentile(99.0);
+        }
+        if let Some(warmup) = self.warmup.as_mut() {
+            warmup.fill_percentiles();
+        }
+        if let Some(spike) = self.spike.as_mut() {
+            spike.burst.fill_percentiles();
+            spike.recovery.fill_percentiles();
+        }
+    }
+
+    // Adds another run's counters to this one, e.g. to combine the results of
+    // several workers. Call finalize afterwards for the derived figures.
+    pub fn merge(&mut self, other: &TestResult) {
+        self.total_requests += other.total_requests;
+        self.successful_requests += other.successful_requests;
+        self.failed_requests += other.failed_requests;
+        self.total_response_time += other.total_response_time;
+        // A finished run reports a min of 0 when nothing succeeded
+        if other.latency_histogram.count() > 0 {
+            self.min_response_time = self.min_response_time.min(other.min_response_time);
+        }
+        self.max_response_time = self.max_response_time.max(other.max_response_time);
+        add_counts(&mut self.status_codes, &other.status_codes);
+
+        for (address, stats) in &other.resolved_addresses {
+            self.resolved_addresses.entry(address.clone()).or_default().merge(stats);
+        }
+
+        add_counts(&mut self.redirect_counts, &other.redirect_counts);
+        for (hop, codes) in other.redirect_hops.iter().enumerate() {
+            if self.redirect_hops.len() <= hop {
+                self.redirect_hops.push(HashMap::new());
+            }
+            add_counts(&mut self.redirect_hops[hop], codes);
+        }
+        add_counts(&mut self.redirects_stopped, &other.redirects_stopped);
+
+        add_counts(&mut self.content_encodings, &other.content_encodings);
+        self.compressed_bytes += other.compressed_bytes;
+        self.decompressed_bytes += other.decompressed_bytes;
+        self.decompression_time += other.decompression_time;
+        self.decompression_errors += other.decompression_errors;
+        // Per-request sizes, the same for every worker running the config
+        self.request_body_bytes = self.request_body_bytes.max(other.request_body_bytes);
+        self.request_body_compressed_bytes = self
+            .request_body_compressed_bytes
+            .max(other.request_body_compressed_bytes);
+
+        self.latency_histogram.merge(&other.latency_histogram);
+        if self.aborted.is_none() {
+            self.aborted = other.aborted.clone();
+        }
+
+        match (self.corrected.as_mut(), &other.corrected) {
+            (Some(corrected), Some(other)) => {
+                corrected.max_response_time = corrected.max_response_time.max(other.max_response_time);
+                corrected.latency_histogram.merge(&other.latency_histogram);
+            }
+            (None, Some(other)) => self.corrected = Some(other.clone()),
+            _ => {}
+        }
+
+        match (self.soak.as_mut(), &other.soak) {
+            (Some(soak), Some(other)) => soak.merge(other),
+            (None, Some(other)) => self.soak = Some(other.clone()),
+            _ => {}
+        }
+
+        // Callers finalize the merged warm-up with its own duration
+        match (self.warmup.as_mut(), &other.warmup) {
+            (Some(warmup), Some(other)) => warmup.merge(other),
+            (None, Some(other)) => self.warmup = Some(other.clone()),
+            _ => {}
+        }
+
+        match (self.spike.as_mut(), &other.spike) {
+            (Some(spike), Some(other)) => spike.merge(other),
+            (None, Some(other)) => self.spike = Some(other.clone()),
+            _ => {}
+        }
+    }
+}
+
+fn add_counts(into: &mut HashMap<String, u32>, from: &HashMap<String, u32>) {
+    for (key, count) in from {
+        *into.entry(key.clone()).or_insert(0) += count;
+    }
+}
+
+// What changed in a running test since the previous delta
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MetricsDelta {
+    // Milliseconds since the test started
+    pub elapsed: f64,
+    pub requests: u32,
+    pub successful_requests: u32,
+    pub failed_requests: u32,
+    pub total_response_time: f64,
+    pub status_codes: HashMap<String, u32>,
+    pub latency_histogram: Histogram,
+}
+
+impl MetricsDelta {
+    pub(crate) fn between(previous: &TestResult, current: &TestResult, elapsed: Duration) -> Self {
+        let status_codes = current
+            .status_codes
+            .iter()
+            .filter_map(|(code, count)| {
+                let change = count - previous.status_codes.get(code).copied().unwrap_or(0);
+                (change > 0).then(|| (code.clone(), change))
+            })
+            .collect();
+
+        MetricsDelta {
+            elapsed: elapsed.as_secs_f64() * 1000.0,
+            requests: current.total_requests - previous.total_requests,
+            successful_requests: current.successful_requests - previous.successful_requests,
+            failed_requests: current.failed_requests - previous.failed_requests,
+            total_response_time: current.total_response_time - previous.total_response_time,
+            status_codes,
+            latency_histogram: current.latency_histogram.delta_since(&previous.latency_histogram),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AddressStats {
+    pub total_requests: u32,
+    pub successful_requests: u32,
+    pub failed_requests: u32,
+    pub errors: u32,
+    pub min_response_time: f64,
+    pub max_response_time: f64,
+    pub avg_response_time: f64,
+    pub total_response_time: f64,
+    pub status_codes: HashMap<String, u32>,
+}
+
+impl AddressStats {
+    fn merge(&mut self, other: &AddressStats) {
+        self.total_requests += other.total_requests;
+        self.successful_requests += other.successful_requests;
+        self.failed_requests += other.failed_requests;
+        self.errors += other.errors;
+        if other.total_requests > other.errors {
+            self.min_response_time = self.min_response_time.min(other.min_response_time);
+        }
+        self.max_response_time = self.max_response_time.max(other.max_response_time);
+        self.total_response_time += other.total_response_time;
+        add_counts(&mut self.status_codes, &other.status_codes);
+    }
+}
+
+impl Default for AddressStats {
+    fn default() -> Self {
+        AddressStats {
+            total_requests: 0,
+            successful_requests: 0,
+            failed_requests: 0,
+            errors: 0,
+            min_response_time: f64::MAX,
+            max_response_time: 0.0,
+            avg_response_time: 0.0,
+            total_response_time: 0.0,
+            status_codes: HashMap::new(),
+        }
+    }
 }
 
 impl Default for TestResult {
@@ -50,92 +674,838 @@ impl Default for TestResult {
             throughput: 0.0,
             test_id: Uuid::new_v4().to_string(),
             timestamp: chrono::Utc::now().timestamp() as u64,
+            resolved_addresses: HashMap::new(),
+            redirect_counts: HashMap::new(),
+            redirect_hops: Vec::new(),
+            redirects_stopped: HashMap::new(),
+            content_encodings: HashMap::new(),
+            compressed_bytes: 0,
+            decompressed_bytes: 0,
+            decompression_time: 0.0,
+            decompression_errors: 0,
+            request_body_bytes: 0,
+            request_body_compressed_bytes: 0,
+            clock_offset: None,
+            clock_uncertainty: None,
+            p50_response_time: 0.0,
+            p90_response_time: 0.0,
+            p95_response_time: 0.0,
+            p99_response_time: 0.0,
+            verdict: None,
+            aborted: None,
+            warmup: None,
+            corrected: None,
+            soak: None,
+            spike: None,
+            latency_histogram: Histogram::new(),
         }
     }
 }
 
-pub async fn run_stress_test(config: TestConfig) -> Result<TestResult, String> {
-    let client = Client::builder()
+// Request bodies are loaded once up front; `Bytes` clones share the buffer.
+enum PreparedBody {
+    Bytes {
+        data: Bytes,
+        content_type: Option<String>,
+        content_encoding: Option<&'static str>,
+    },
+    Multipart(Vec<PreparedPart>),
+}
+
+struct PreparedPart {
+    name: String,
+    data: Bytes,
+    file_name: Option<String>,
+    content_type: Option<String>,
+}
+
+async fn read_body_file(path: &str) -> Result<Bytes, String> {
+    tokio::fs::read(path)
+        .await
+        .map(Bytes::from)
+        .map_err(|e| format!("Failed to read body file {}: {}", path, e))
+}
+
+async fn prepare_body(config: &TestConfig) -> Result<Option<PreparedBody>, String> {
+    let source = match (&config.body, &config.body_source) {
+        (Some(_), Some(_)) => return Err("Specify either body or body_source, not both".to_string()),
+        (None, None) => return Ok(None),
+        (Some(body), None) => {
+            return Ok(Some(PreparedBody::Bytes {
+                data: Bytes::from(body.clone()),
+                content_type: None,
+                content_encoding: None,
+            }))
+        }
+        (None, Some(source)) => source,
+    };
+
+    // These bodies set their own Content-Type, and a multipart one carries the
+    // boundary the server needs to parse it
+    if matches!(source, RequestBody::Form { .. } | RequestBody::Multipart { .. })
+        && config.headers.keys().any(|key| key.eq_ignore_ascii_case("content-type"))
+    {
+        return Err("Remove the Content-Type header; form and multipart bodies set their own".to_string());
+    }
+
+    let prepared = match source {
+        RequestBody::File { path, content_type } => PreparedBody::Bytes {
+            data: read_body_file(path).await?,
+            content_type: content_type.clone(),
+            content_encoding: None,
+        },
+        RequestBody::Base64 { data, content_type } => PreparedBody::Bytes {
+            data: base64::engine::general_purpose::STANDARD
+                .decode(data.trim())
+                .map(Bytes::from)
+                .map_err(|e| format!("Invalid base64 body: {}", e))?,
+            content_type: content_type.clone(),
+            content_encoding: None,
+        },
+        RequestBody::Form { fields } => {
+            let encoded = url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(fields)
+                .finish();
+            PreparedBody::Bytes {
+                data: Bytes::from(encoded),
+                content_type: Some("application/x-www-form-urlencoded".to_string()),
+                content_encoding: None,
+            }
+        }
+        RequestBody::Multipart { parts } => {
+            let mut prepared_parts = Vec::with_capacity(parts.len());
+            for part in parts {
+                let data = match (&part.value, &part.file) {
+                    (Some(value), None) => Bytes::from(value.clone()),
+                    (None, Some(path)) => read_body_file(path).await?,
+                    _ => {
+                        return Err(format!(
+                            "Multipart part '{}' needs exactly one of value or file",
+                            part.name
+                        ))
+                    }
+                };
+                // File parts default to their path's file name
+                let file_name = part.file_name.clone().or_else(|| {
+                    part.file.as_ref().and_then(|path| {
+                        std::path::Path::new(path)
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                    })
+                });
+                prepared_parts.push(PreparedPart {
+                    name: part.name.clone(),
+                    data,
+                    file_name,
+                    content_type: part.content_type.clone(),
+                });
+            }
+            PreparedBody::Multipart(prepared_parts)
+        }
+    };
+
+    Ok(Some(prepared))
+}
+
+fn compress_body(body: PreparedBody) -> Result<PreparedBody, String> {
+    match body {
+        PreparedBody::Bytes { data, content_type, .. } => Ok(PreparedBody::Bytes {
+            data: Bytes::from(compression::gzip(&data)?),
+            content_type,
+            content_encoding: Some("gzip"),
+        }),
+        PreparedBody::Multipart(_) => Err("Body compression is not supported for multipart bodies".to_string()),
+    }
+}
+
+fn build_multipart(parts: &[PreparedPart]) -> Result<Form, reqwest::Error> {
+    let mut form = Form::new();
+    for part in parts {
+        let mut field = Part::stream_with_length(Body::from(part.data.clone()), part.data.len() as u64);
+        if let Some(file_name) = &part.file_name {
+            field = field.file_name(file_name.clone());
+        }
+        if let Some(content_type) = &part.content_type {
+            field = field.mime_str(content_type)?;
+        }
+        form = form.part(part.name.clone(), field);
+    }
+    Ok(form)
+}
+
+struct RequestSpec {
+    method: Method,
+    url: Url,
+    headers: HashMap<String, String>,
+    body: Option<PreparedBody>,
+    redirect_policy: RedirectPolicy,
+    raw: Option<Bytes>,
+    timeout: Duration,
+    accept_encoding: Option<String>,
+}
+
+// Where a request is sent: the shared client, or one pinned to a
+// single overridden address.
+struct Endpoint {
+    address: Option<String>,
+    pinned: Option<SocketAddr>,
+    client: Client,
+}
+
+#[derive(Clone)]
+struct RequestOutcome {
+    status: StatusCode,
+    // When the final response's headers arrived; latency is measured to here
+    // so reading the body doesn't count
+    responded: Instant,
+    // Status codes of the redirect responses that were followed
+    hops: Vec<u16>,
+    stopped: Option<&'static str>,
+    compression: Option<CompressionSample>,
+}
+
+#[derive(Clone)]
+struct CompressionSample {
+    encoding: String,
+    compressed_bytes: u64,
+    decompressed_bytes: u64,
+    decompression_time: f64,
+    failed: bool,
+}
+
+// Sends one request, following redirects by hand so each hop can be recorded.
+async fn send_request(endpoint: &Endpoint, spec: &RequestSpec) -> Result<RequestOutcome, String> {
+    if let Some(raw) = &spec.raw {
+        let (status, responded) = raw_http::send(&spec.url, endpoint.pinned, raw, spec.timeout).await?;
+        return Ok(RequestOutcome {
+            status,
+            responded,
+            hops: Vec::new(),
+            stopped: None,
+            compression: None,
+        });
+    }
+
+    let mut method = spec.method.clone();
+    let mut url = spec.url.clone();
+    let mut send_body = spec.body.is_some();
+    let mut hops = Vec::new();
+    let mut visited = vec![url.clone()];
+    // Set once a redirect leaves the original origin, as reqwest's own policy
+    // does, so credentials don't follow it to another host
+    let mut strip_sensitive = false;
+
+    let (response, mut outcome) = loop {
+        let mut req_builder = endpoint.client.request(method.clone(), url.clone());
+
+        // Add body if present
+        match spec.body.as_ref().filter(|_| send_body) {
+            Some(PreparedBody::Bytes { data, content_type, content_encoding }) => {
+                let has_content_type = spec.headers.keys().any(|key| key.eq_ignore_ascii_case("content-type"));
+                if let Some(content_type) = content_type.as_ref().filter(|_| !has_content_type) {
+                    req_builder = req_builder.header(CONTENT_TYPE, content_type);
+                }
+                if let Some(content_encoding) = content_encoding {
+                    req_builder = req_builder.header(CONTENT_ENCODING, *content_encoding);
+                }
+                req_builder = req_builder.body(data.clone());
+            }
+            Some(PreparedBody::Multipart(parts)) => {
+                let form = build_multipart(parts).map_err(|e| e.to_string())?;
+                req_builder = req_builder.multipart(form);
+            }
+            None => {}
+        }
+
+        if let Some(accept_encoding) = &spec.accept_encoding {
+            req_builder = req_builder.header(ACCEPT_ENCODING, accept_encoding);
+        }
+
+        // Add headers
+        for (key, value) in &spec.headers {
+            if strip_sensitive && is_sensitive_header(key) {
+                continue;
+            }
+            req_builder = req_builder.header(key, value);
+        }
+
+        let response = req_builder.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let mut outcome = RequestOutcome {
+            status,
+            responded: Instant::now(),
+            hops,
+            stopped: None,
+            compression: None,
+        };
+
+        if !status.is_redirection() || spec.redirect_policy == RedirectPolicy::None {
+            break (response, outcome);
+        }
+
+        let next = match response
+            .headers()
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|location| url.join(location).ok())
+        {
+            Some(next) => next,
+            None => break (response, outcome),
+        };
+
+        if outcome.hops.len() >= spec.redirect_policy.max_redirects() {
+            outcome.stopped = Some("limit");
+        } else if matches!(spec.redirect_policy, RedirectPolicy::SameHost { .. })
+            && next.host_str() != url.host_str()
+        {
+            outcome.stopped = Some("cross_host");
+        } else if visited.contains(&next) {
+            outcome.stopped = Some("loop");
+        }
+        if outcome.stopped.is_some() {
+            break (response, outcome);
+        }
+
+        hops = outcome.hops;
+        hops.push(status.as_u16());
+
+        // Browsers downgrade to GET on 303, and on 301/302 after a POST
+        if status == StatusCode::SEE_OTHER
+            || (method == Method::POST
+                && (status == StatusCode::MOVED_PERMANENTLY || status == StatusCode::FOUND))
+        {
+            method = Method::GET;
+            send_body = false;
+        }
+
+        if next.scheme() != url.scheme()
+            || next.host_str() != url.host_str()
+            || next.port_or_known_default() != url.port_or_known_default()
+        {
+            strip_sensitive = true;
+        }
+
+        // Read the redirect's body so its connection can be reused
+        let _ = response.bytes().await;
+
+        visited.push(next.clone());
+        url = next;
+    };
+
+    // Compression is only measured when we asked for it, since it means
+    // reading the whole body. That happens after `responded`, so latency
+    // compares across runs with and without it.
+    if spec.accept_encoding.is_some() {
+        let encoding = response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.trim().to_ascii_lowercase())
+            .unwrap_or_else(|| "identity".to_string());
+        let body = response.bytes().await.map_err(|e| e.to_string())?;
+
+        let mut sample = CompressionSample {
+            encoding,
+            compressed_bytes: body.len() as u64,
+            decompressed_bytes: body.len() as u64,
+            decompression_time: 0.0,
+            failed: false,
+        };
+        if sample.encoding != "identity" {
+            // Decoding is CPU-bound, so it stays off the runtime's workers
+            let encoding = sample.encoding.clone();
+            let (decoded, decode_time) = task::spawn_blocking(move || {
+                let decode_start = Instant::now();
+                let decoded = compression::decoded_len(&encoding, &body);
+                (decoded, decode_start.elapsed())
+            })
+            .await
+            .map_err(|e| format!("Decompression failed: {}", e))?;
+            match decoded {
+                Ok(length) => sample.decompressed_bytes = length,
+                Err(_) => sample.failed = true,
+            }
+            sample.decompression_time = decode_time.as_secs_f64() * 1000.0; // in ms
+        }
+        outcome.compression = Some(sample);
+    }
+
+    Ok(outcome)
+}
+
+// Headers reqwest drops when a redirect crosses to another origin
+fn is_sensitive_header(name: &str) -> bool {
+    ["authorization", "cookie", "cookie2", "proxy-authorization", "www-authenticate"]
+        .iter()
+        .any(|sensitive| name.eq_ignore_ascii_case(sensitive))
+}
+
+fn parse_override_addrs(host: &str, addrs: &[String]) -> Result<Vec<SocketAddr>, String> {
+    addrs
+        .iter()
+        .map(|addr| {
+            addr.trim()
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .map(|ip| SocketAddr::new(ip, 0))
+                .map_err(|e| format!("Invalid address '{}' for host {}: {}", addr, host, e))
+        })
+        .collect()
+}
+
+// Builds an HTTP client honouring the configured resolve overrides. When `pinned`
+// is set, the target host resolves to that single address only.
+fn build_client(
+    config: &TestConfig,
+    target_host: &str,
+    pinned: Option<SocketAddr>,
+) -> Result<Client, String> {
+    // Redirects are followed in send_request so every hop gets recorded
+    let mut builder = Client::builder()
         .timeout(Duration::from_millis(config.timeout_ms as u64))
+        .redirect(redirect::Policy::none());
+
+    for (host, addrs) in &config.resolve {
+        if host.eq_ignore_ascii_case(target_host) {
+            if let Some(addr) = pinned {
+                builder = builder.resolve(target_host, addr);
+                continue;
+            }
+        }
+        let addrs = parse_override_addrs(host, addrs)?;
+        if !addrs.is_empty() {
+            builder = builder.resolve_to_addrs(&host.to_lowercase(), &addrs);
+        }
+    }
+
+    builder
         .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+// Accepts any valid HTTP token. The standard verbs are matched
+// case-insensitively; extension methods such as PROPFIND or PURGE are sent as given.
+fn parse_method(method: &str) -> Result<Method, String> {
+    let method = method.trim();
+    let upper = method.to_ascii_uppercase();
+    let token = match upper.as_str() {
+        "GET" | "POST" | "PUT" | "DELETE" | "PATCH" | "HEAD" | "OPTIONS" | "CONNECT" | "TRACE" => &upper,
+        _ => method,
+    };
+
+    Method::from_bytes(token.as_bytes()).map_err(|_| format!("Invalid HTTP method: {}", method))
+}
+
+// Adds one finished request to the running totals
+fn record_request(
+    results: &mut TestResult,
+    address: Option<&str>,
+    response: Result<RequestOutcome, String>,
+    request_duration: f64,
+) {
+    results.total_requests += 1;
+
+    if let Some(address) = address {
+        let stats = results.resolved_addresses.entry(address.to_string()).or_default();
+        stats.total_requests += 1;
+        match &response {
+            Ok(outcome) => {
+                let status = outcome.status;
+                *stats.status_codes.entry(status.as_u16().to_string()).or_insert(0) += 1;
+                if status.is_success() {
+                    stats.successful_requests += 1;
+                } else {
+                    stats.failed_requests += 1;
+                }
+                stats.min_response_time = stats.min_response_time.min(request_duration);
+                stats.max_response_time = stats.max_response_time.max(request_duration);
+                stats.total_response_time += request_duration;
+            }
+            Err(_) => {
+                stats.failed_requests += 1;
+                stats.errors += 1;
+                *stats.status_codes.entry("error".to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    match response {
+        Ok(outcome) => {
+            let status = outcome.status;
+            let status_code = status.as_u16().to_string();
+
+            // Update status code count
+            *results.status_codes.entry(status_code).or_insert(0) += 1;
+
+            if status.is_success() {
+                results.successful_requests += 1;
+            } else {
+                results.failed_requests += 1;
+            }
+
+            // Update redirect statistics
+            *results.redirect_counts.entry(outcome.hops.len().to_string()).or_insert(0) += 1;
+            for (hop, code) in outcome.hops.iter().enumerate() {
+                if results.redirect_hops.len() <= hop {
+                    results.redirect_hops.push(HashMap::new());
+                }
+                *results.redirect_hops[hop].entry(code.to_string()).or_insert(0) += 1;
+            }
+            if let Some(reason) = outcome.stopped {
+                *results.redirects_stopped.entry(reason.to_string()).or_insert(0) += 1;
+            }
+
+            // Update compression statistics
+            if let Some(sample) = outcome.compression {
+                results.compressed_bytes += sample.compressed_bytes;
+                results.decompressed_bytes += sample.decompressed_bytes;
+                results.decompression_time += sample.decompression_time;
+                if sample.failed {
+                    results.decompression_errors += 1;
+                }
+                *results.content_encodings.entry(sample.encoding).or_insert(0) += 1;
+            }
+
+            // Update response time statistics
+            if request_duration < results.min_response_time {
+                results.min_response_time = request_duration;
+            }
+            if request_duration > results.max_response_time {
+                results.max_response_time = request_duration;
+            }
+            results.total_response_time += request_duration;
+            results.latency_histogram.record(request_duration);
+        }
+        Err(_) => {
+            results.failed_requests += 1;
+            *results.status_codes.entry("error".to_string()).or_insert(0) += 1;
+        }
+    }
+}
+
+// Running totals at one point in time, for working out a sliding window
+struct WindowSample {
+    at: Instant,
+    requests: u32,
+    failed_requests: u32,
+    latency_histogram: Histogram,
+}
+
+impl WindowSample {
+    fn of(result: &TestResult) -> Self {
+        WindowSample {
+            at: Instant::now(),
+            requests: result.total_requests,
+            failed_requests: result.failed_requests,
+            latency_histogram: result.latency_histogram.clone(),
+        }
+    }
+}
+
+// Trips `abort` once the trailing window's error rate or p99 crosses a limit
+fn watch_abort_conditions(
+    conditions: AbortConditions,
+    results: Arc<Mutex<TestResult>>,
+    start_time: Instant,
+    abort: AbortSignal,
+) -> task::JoinHandle<()> {
+    task::spawn(async move {
+        let window = Duration::from_millis(conditions.window_ms);
+        let mut samples = std::collections::VecDeque::from([WindowSample {
+            at: start_time,
+            requests: 0,
+            failed_requests: 0,
+            latency_histogram: Histogram::new(),
+        }]);
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+            let current = WindowSample::of(&*results.lock().await);
+
+            // Keep the newest sample from before the window as its baseline
+            while samples.len() > 1 && current.at.duration_since(samples[1].at) >= window {
+                samples.pop_front();
+            }
+            let baseline = &samples[0];
+
+            let requests = current.requests - baseline.requests;
+            if current.at.duration_since(start_time) >= window && requests >= conditions.min_requests.max(1) {
+                let seconds = window.as_secs_f64();
+                let error_rate = (current.failed_requests - baseline.failed_requests) as f64 / requests as f64 * 100.0;
+                if let Some(limit) = conditions.max_error_rate.filter(|limit| error_rate > *limit) {
+                    abort.abort(format!(
+                        "Error rate {:.1}% over the last {:.0}s exceeded {}%",
+                        error_rate, seconds, limit
+                    ));
+                    break;
+                }
+
+                let p99 = current.latency_histogram.delta_since(&baseline.latency_histogram).percentile(99.0);
+                if let Some(limit) = conditions.max_p99_ms.filter(|limit| p99 > *limit) {
+                    abort.abort(format!("p99 of {:.0}ms over the last {:.0}s exceeded {}ms", p99, seconds, limit));
+                    break;
+                }
+            }
+
+            samples.push_back(current);
+        }
+    })
+}
+
+pub async fn run_stress_test(config: TestConfig) -> Result<TestResult, String> {
+    run_stress_test_with_progress(config, None, AbortSignal::new()).await
+}
+
+// Same as run_stress_test, additionally sending a MetricsDelta about once a
+// second while the test runs. Tripping `abort` stops sending new requests;
+// the result covers what completed and carries the reason.
+pub async fn run_stress_test_with_progress(
+    config: TestConfig,
+    progress: Option<mpsc::UnboundedSender<MetricsDelta>>,
+    abort: AbortSignal,
+) -> Result<TestResult, String> {
+    if config.concurrency == 0 {
+        return Err("Concurrency must be at least 1".to_string());
+    }
+    thresholds::validate(&config.thresholds)?;
+    if let Some(think_time) = &config.think_time {
+        think_time.validate()?;
+    }
+    if let Some(spike) = &config.spike {
+        spike.validate(config.rate_per_second)?;
+    }
+    if config.raw_request.is_some() {
+        // The raw request goes out exactly as written, so these would be ignored
+        let ignored: Vec<&str> = [
+            ("method", !config.method.eq_ignore_ascii_case("GET")),
+            ("headers", !config.headers.is_empty()),
+            ("body", config.body.is_some()),
+            ("body_source", config.body_source.is_some()),
+            ("compress_body", config.compress_body),
+            ("accept_encoding", !config.accept_encoding.is_empty()),
+            ("redirect_policy", config.redirect_policy != RedirectPolicy::default()),
+        ]
+        .into_iter()
+        .filter(|(_, set)| *set)
+        .map(|(field, _)| field)
+        .collect();
+        if !ignored.is_empty() {
+            return Err(format!(
+                "raw_request can't be combined with {}; write them into the raw request instead",
+                ignored.join(", ")
+            ));
+        }
+    }
+    if let Some(interval) = config.expected_interval_ms {
+        // Shorter intervals would backfill millions of samples per slow response
+        if !interval.is_finite() || interval < MIN_EXPECTED_INTERVAL_MS {
+            return Err(format!("expected_interval_ms must be at least {}", MIN_EXPECTED_INTERVAL_MS));
+        }
+    }
 
     let url = Url::parse(&config.url).map_err(|e| format!("Invalid URL: {}", e))?;
-    let method = match config.method.to_uppercase().as_str() {
-        "GET" => Method::GET,
-        "POST" => Method::POST,
-        "PUT" => Method::PUT,
-        "DELETE" => Method::DELETE,
-        "PATCH" => Method::PATCH,
-        "HEAD" => Method::HEAD,
-        "OPTIONS" => Method::OPTIONS,
-        _ => return Err(format!("Unsupported HTTP method: {}", config.method)),
+    let host = url
+        .host_str()
+        .ok_or_else(|| "Invalid URL: missing host".to_string())?
+        .to_string();
+
+    // One client per overridden address so each connection pool sticks to a
+    // single backend; otherwise a single shared client.
+    let target_addrs = config
+        .resolve
+        .iter()
+        .find(|(h, _)| h.eq_ignore_ascii_case(&host))
+        .map(|(h, addrs)| parse_override_addrs(h, addrs))
+        .transpose()?
+        .unwrap_or_default();
+
+    let endpoints: Vec<Endpoint> = if target_addrs.is_empty() {
+        vec![Endpoint {
+            address: None,
+            pinned: None,
+            client: build_client(&config, &host, None)?,
+        }]
+    } else {
+        target_addrs
+            .iter()
+            .map(|addr| {
+                Ok(Endpoint {
+                    address: Some(addr.ip().to_string()),
+                    pinned: Some(*addr),
+                    client: build_client(&config, &host, Some(*addr))?,
+                })
+            })
+            .collect::<Result<_, String>>()?
     };
+    // Each request takes the next address in turn, so every address is hit
+    // however few workers there are
+    let endpoints = Arc::new(endpoints);
+    let next_endpoint = Arc::new(AtomicUsize::new(0));
+    let method = parse_method(&config.method)?;
+    let raw = config
+        .raw_request
+        .as_ref()
+        .map(|template| Bytes::from(raw_http::render_template(template, &url)));
 
-    let results = Arc::new(Mutex::new(TestResult::default()));
+    let mut initial_results = TestResult::default();
+    let mut body = prepare_body(&config).await?;
+    if let Some(PreparedBody::Bytes { data, .. }) = &body {
+        initial_results.request_body_bytes = data.len() as u64;
+    }
+    if config.compress_body {
+        body = body.map(compress_body).transpose()?;
+    }
+    if let Some(PreparedBody::Bytes { data, .. }) = &body {
+        initial_results.request_body_compressed_bytes = data.len() as u64;
+    }
+
+    let pacing_rate = config.rate_per_second.filter(|rate| *rate > 0.0);
+    let expected_interval = config.expected_interval_ms;
+    let mut warmup_results = TestResult::default();
+    if pacing_rate.is_some() || expected_interval.is_some() {
+        initial_results.corrected = Some(CorrectedLatency::default());
+        warmup_results.corrected = Some(CorrectedLatency::default());
+    }
+
+    let test_id = initial_results.test_id.clone();
+    let results = Arc::new(Mutex::new(initial_results));
     let start_time = Instant::now();
+    let spike = config
+        .spike
+        .clone()
+        .map(|profile| Arc::new(SpikeRecorder::new(profile, start_time, &warmup_results)));
+    let warmup_results = Arc::new(Mutex::new(warmup_results));
+    let gate = Arc::new(WarmupGate::new(config.warmup.as_ref(), start_time));
+    let soak = match config.soak.clone() {
+        Some(soak) => Some(SoakMonitor::start(soak, Arc::clone(&results), start_time, &test_id).await?),
+        None => None,
+    };
 
     // Create a vector to hold all task handles
     let mut handles = Vec::new();
 
+    let spec = Arc::new(RequestSpec {
+        method,
+        url,
+        headers: config.headers.clone(),
+        body,
+        redirect_policy: config.redirect_policy.clone(),
+        raw,
+        timeout: Duration::from_millis(config.timeout_ms as u64),
+        accept_encoding: Some(config.accept_encoding.join(", ")).filter(|value| !value.is_empty()),
+    });
+
+    let duration = config.duration_ms.map(Duration::from_millis);
+    let pacer = pacing_rate.map(|rate| {
+        Arc::new(Pacer {
+            start: start_time,
+            rate,
+            issued: AtomicU64::new(0),
+            spike: config
+                .spike
+                .clone()
+                .map(|profile| (profile, std::sync::Mutex::new(Duration::ZERO))),
+        })
+    });
+
     // Spawn concurrent tasks
-    for _ in 0..config.concurrency {
-        let client = client.clone();
-        let url = url.clone();
-        let method = method.clone();
-        let headers = config.headers.clone();
-        let body = config.body.clone();
-        let requests_per_task = config.requests_per_client / config.concurrency;
+    for task in 0..config.concurrency {
+        let endpoints = Arc::clone(&endpoints);
+        let next_endpoint = Arc::clone(&next_endpoint);
+        let spec = Arc::clone(&spec);
+        // The first tasks take one extra each, so a share that doesn't divide
+        // evenly by the concurrency is still run in full
+        let requests_per_task = config.requests_per_client / config.concurrency
+            + u32::from(task < config.requests_per_client % config.concurrency);
         let results = Arc::clone(&results);
+        let warmup_results = Arc::clone(&warmup_results);
+        let gate = Arc::clone(&gate);
+        let pacer = pacer.clone();
+        let spike = spike.clone();
+        let think_time = config.think_time.clone();
+        let pacing = config.pacing_ms.map(Duration::from_millis);
+        let mut stop = abort.subscribe();
 
         let handle = task::spawn(async move {
-            for _ in 0..requests_per_task {
+            let mut sent = 0;
+            loop {
+                if stop.borrow().is_some() {
+                    break;
+                }
+                // Warm-up requests don't count towards either limit
+                let deadline = duration.and_then(|duration| gate.deadline(duration));
+                match duration {
+                    Some(_) if deadline.is_some_and(|deadline| Instant::now() >= deadline) => break,
+                    None if sent >= requests_per_task => break,
+                    _ => {}
+                }
+                let slot = pacer.as_ref().map(|pacer| pacer.next_slot());
+                if let Some(slot) = slot {
+                    if matches!(deadline, Some(deadline) if slot >= deadline) {
+                        break;
+                    }
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(slot.into()) => {}
+                        _ = stop.changed() => break,
+                    }
+                }
                 let request_start = Instant::now();
-                let mut req_builder = client.request(method.clone(), url.clone());
-
-                // Add headers
-                for (key, value) in &headers {
-                    req_builder = req_builder.header(key, value);
+                let warming = gate.claim(request_start);
+                if !warming {
+                    sent += 1;
                 }
 
-                // Add body if present
-                if let Some(body_content) = &body {
-                    req_builder = req_builder.body(body_content.clone());
+                // Send the request
+                let endpoint = &endpoints[next_endpoint.fetch_add(1, Ordering::Relaxed) % endpoints.len()];
+                let response = send_request(endpoint, &spec).await;
+                let responded = response.as_ref().map_or_else(|_| Instant::now(), |outcome| outcome.responded);
+                let request_duration = responded.duration_since(request_start).as_secs_f64() * 1000.0; // in ms
+
+                let completed = response.is_ok();
+                // A late request waited from its slot, not from when it went out
+                let (corrected_ms, corrected_interval) = match slot {
+                    Some(slot) => (responded.saturating_duration_since(slot).as_secs_f64() * 1000.0, None),
+                    None => (request_duration, expected_interval),
+                };
+                // Spike runs also file a request under the burst or recovery
+                // window it was scheduled in
+                let window = spike
+                    .as_ref()
+                    .zip(slot)
+                    .filter(|_| !warming)
+                    .and_then(|(spike, slot)| spike.results_for(slot));
+                if let Some(window) = window {
+                    let mut results = window.lock().await;
+                    record_request(&mut results, endpoint.address.as_deref(), response.clone(), request_duration);
+                    if let Some(corrected) = results.corrected.as_mut().filter(|_| completed) {
+                        corrected.record(corrected_ms, corrected_interval);
+                    }
+                }
+                {
+                    let mut results = if warming { warmup_results.lock().await } else { results.lock().await };
+                    record_request(&mut results, endpoint.address.as_deref(), response, request_duration);
+                    if let Some(corrected) = results.corrected.as_mut().filter(|_| completed) {
+                        corrected.record(corrected_ms, corrected_interval);
+                    }
                 }
 
-                // Send the request
-                let response = req_builder.send().await;
-                let request_duration = request_start.elapsed().as_secs_f64() * 1000.0; // in ms
-
-                let mut results = results.lock().await;
-                results.total_requests += 1;
-
-                match response {
-                    Ok(resp) => {
-                        let status = resp.status();
-                        let status_code = status.as_u16().to_string();
-
-                        // Update status code count
-                        *results.status_codes.entry(status_code).or_insert(0) += 1;
-
-                        if status.is_success() {
-                            results.successful_requests += 1;
-                        } else {
-                            results.failed_requests += 1;
-                        }
-
-                        // Update response time statistics
-                        if request_duration < results.min_response_time {
-                            results.min_response_time = request_duration;
-                        }
-                        if request_duration > results.max_response_time {
-                            results.max_response_time = request_duration;
-                        }
-                        results.total_response_time += request_duration;
+                // Think, then wait out the rest of the pacing interval; not
+                // after a task's last request
+                if duration.is_none() && sent >= requests_per_task {
+                    break;
+                }
+                let think_until = Instant::now() + think_time.as_ref().map(ThinkTime::sample).unwrap_or_default();
+                let wake = pacing.map_or(think_until, |pacing| think_until.max(request_start + pacing));
+                if wake > Instant::now() {
+                    if deadline.is_some_and(|deadline| wake >= deadline) {
+                        break;
                     }
-                    Err(_) => {
-                        results.failed_requests += 1;
-                        *results.status_codes.entry("error".to_string()).or_insert(0) += 1;
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(wake.into()) => {}
+                        _ = stop.changed() => break,
                     }
                 }
             }
@@ -144,28 +1514,439 @@ pub async fn run_stress_test(config: TestConfig) -> Result<TestResult, String> {
         handles.push(handle);
     }
 
-    // Wait for all tasks to complete
+    // Stream deltas while the workers run
+    let reporter = progress.map(|progress| {
+        let results = Arc::clone(&results);
+        task::spawn(async move {
+            let mut previous = TestResult::default();
+            let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let current = results.lock().await.clone();
+                let delta = MetricsDelta::between(&previous, &current, start_time.elapsed());
+                previous = current;
+                if progress.send(delta).is_err() {
+                    break;
+                }
+            }
+        })
+    });
+
+    let monitor = config
+        .abort_conditions
+        .clone()
+        .map(|conditions| watch_abort_conditions(conditions, Arc::clone(&results), start_time, abort.clone()));
+
+    // Wait for all tasks to complete. A failed task stops the rest, and the
+    // error is returned once the background tasks are stopped too.
+    let mut failure = None;
     for handle in handles {
-        if let Err(e) = handle.await {
-            return Err(format!("Task failed: {}", e));
+        if failure.is_some() {
+            handle.abort();
+        } else if let Err(e) = handle.await {
+            failure = Some(format!("Task failed: {}", e));
         }
     }
+    if let Some(reporter) = reporter {
+        reporter.abort();
+    }
+    if let Some(monitor) = monitor {
+        monitor.abort();
+    }
+    let soak = match soak {
+        Some(soak) => Some(soak.finish().await),
+        None => None,
+    };
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    let soak = soak.transpose()?;
 
-    // Calculate final statistics
+    // Calculate final statistics. The measured run starts where the warm-up
+    // ends, or never did if the run ended first.
+    let end_time = Instant::now();
+    let measured_from = gate.measured_from.get().copied().unwrap_or(end_time).min(end_time);
     let mut final_results = results.lock().await;
-    let total_duration = start_time.elapsed().as_secs_f64() * 1000.0; // in ms
-    final_results.duration = total_duration;
+    final_results.finalize(end_time.duration_since(measured_from).as_secs_f64() * 1000.0); // in ms
+    if config.warmup.is_some() {
+        let mut warmup = warmup_results.lock().await.clone();
+        warmup.finalize(measured_from.duration_since(start_time).as_secs_f64() * 1000.0);
+        final_results.warmup = Some(Box::new(warmup));
+    }
+    if let Some(spike) = spike {
+        final_results.spike = Some(Box::new(spike.finish(measured_from, end_time).await));
+    }
+    final_results.aborted = abort.reason();
+    final_results.soak = soak;
+    if !config.thresholds.is_empty() {
+        final_results.verdict = Some(thresholds::evaluate(&config.thresholds, &final_results)?);
+    }
 
-    if final_results.total_requests > 0 {
-        final_results.avg_response_time =
-            final_results.total_response_time / final_results.total_requests as f64;
-        final_results.throughput = (final_results.total_requests as f64 / total_duration) * 1000.0; // requests per second
+    Ok(final_results.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // A request as the test server got it
+    struct Received {
+        local_ip: IpAddr,
+        at: Instant,
+        text: String,
     }
 
-    // Handle edge case where no requests were successful
-    if final_results.min_response_time == f64::MAX {
-        final_results.min_response_time = 0.0;
+    type Log = Arc<std::sync::Mutex<Vec<Received>>>;
+
+    // Answers each request with `respond(request)` and closes the connection.
+    // Returns the port and everything received so far.
+    async fn serve<F>(bind: &str, respond: F) -> (u16, Log)
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind((bind, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let log: Log = Arc::default();
+        let respond = Arc::new(respond);
+        let received = Arc::clone(&log);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (respond, received) = (Arc::clone(&respond), Arc::clone(&received));
+                tokio::spawn(async move {
+                    let Some(text) = read_request(&mut stream).await else { return };
+                    let response = respond(&text);
+                    received.lock().unwrap().push(Received {
+                        local_ip: stream.local_addr().unwrap().ip(),
+                        at: Instant::now(),
+                        text,
+                    });
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        (port, log)
     }
 
-    Ok(final_results.clone())
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> Option<String> {
+        let mut data = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = stream.read(&mut buffer).await.ok().filter(|read| *read > 0)?;
+            data.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&data).into_owned();
+            let Some(head_end) = text.find("\r\n\r\n") else { continue };
+            let head = text[..head_end].to_ascii_lowercase();
+            let content_length = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|value| value.trim().parse::<usize>().ok());
+            let complete = match content_length {
+                Some(length) => data.len() >= head_end + 4 + length,
+                None if head.contains("transfer-encoding: chunked") => text.ends_with("\r\n0\r\n\r\n"),
+                None => true,
+            };
+            if complete {
+                return Some(text);
+            }
+        }
+    }
+
+    fn respond(status: &str, headers: &str) -> String {
+        format!("HTTP/1.1 {}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n", status, headers)
+    }
+
+    fn ok(_: &str) -> String {
+        respond("200 OK", "")
+    }
+
+    fn path(request: &str) -> &str {
+        request.split(' ').nth(1).unwrap_or_default()
+    }
+
+    fn config(url: &str, extra: serde_json::Value) -> TestConfig {
+        let mut config = serde_json::json!({
+            "url": url,
+            "method": "GET",
+            "headers": {},
+            "body": null,
+            "requests_per_client": 1,
+            "concurrency": 1,
+            "timeout_ms": 2000,
+        });
+        config.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn raw_requests_reject_settings_they_would_ignore() {
+        let raw = serde_json::json!("GET {{path}} HTTP/1.1\r\nHost: {{host}}\r\n\r\n");
+        let cases = [
+            ("method", serde_json::json!({ "raw_request": raw, "method": "POST" })),
+            ("headers", serde_json::json!({ "raw_request": raw, "headers": { "X-Test": "1" } })),
+            ("body", serde_json::json!({ "raw_request": raw, "body": "hello" })),
+            ("compress_body", serde_json::json!({ "raw_request": raw, "compress_body": true })),
+            ("redirect_policy", serde_json::json!({ "raw_request": raw, "redirect_policy": { "mode": "none" } })),
+        ];
+        for (field, extra) in cases {
+            let error = run_stress_test(config("http://127.0.0.1:9/", extra)).await.unwrap_err();
+            assert!(error.contains(field), "{}: {}", field, error);
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_take_resolved_addresses_in_turn() {
+        let (port, log) = serve("0.0.0.0", ok).await;
+        let result = run_stress_test(config(
+            &format!("http://loadtest.invalid:{}/", port),
+            serde_json::json!({
+                "requests_per_client": 6,
+                "concurrency": 2,
+                "resolve": { "loadtest.invalid": ["127.0.0.1", "127.0.0.2"] },
+            }),
+        ))
+        .await
+        .unwrap();
+
+        assert_eq!(result.successful_requests, 6);
+        for address in ["127.0.0.1", "127.0.0.2"] {
+            assert_eq!(result.resolved_addresses[address].total_requests, 3, "{}", address);
+            let ip: IpAddr = address.parse().unwrap();
+            let served = log.lock().unwrap().iter().filter(|received| received.local_ip == ip).count();
+            assert_eq!(served, 3, "{}", address);
+        }
+        // The overrides only change where requests go, not the Host they name
+        let host = format!("host: loadtest.invalid:{}", port);
+        assert!(log.lock().unwrap().iter().all(|received| received.text.to_ascii_lowercase().contains(&host)));
+    }
+
+    #[tokio::test]
+    async fn redirect_hops_are_recorded_and_credentials_stay_on_the_origin() {
+        let (other_port, other_log) = serve("127.0.0.1", ok).await;
+        let (port, log) = serve("127.0.0.1", move |request| match path(request) {
+            "/start" => respond("302 Found", "Location: /next\r\n"),
+            _ => respond(
+                "307 Temporary Redirect",
+                &format!("Location: http://127.0.0.1:{}/end\r\n", other_port),
+            ),
+        })
+        .await;
+        let result = run_stress_test(config(
+            &format!("http://127.0.0.1:{}/start", port),
+            serde_json::json!({ "headers": { "Authorization": "Bearer secret", "X-Trace": "1" } }),
+        ))
+        .await
+        .unwrap();
+
+        assert_eq!(result.status_codes, HashMap::from([("200".to_string(), 1)]));
+        assert_eq!(result.redirect_counts, HashMap::from([("2".to_string(), 1)]));
+        assert_eq!(
+            result.redirect_hops,
+            vec![HashMap::from([("302".to_string(), 1)]), HashMap::from([("307".to_string(), 1)])]
+        );
+        assert!(result.redirects_stopped.is_empty());
+
+        let lowercase = |log: &Log| -> Vec<String> {
+            log.lock().unwrap().iter().map(|received| received.text.to_ascii_lowercase()).collect()
+        };
+        let same_origin = lowercase(&log);
+        assert_eq!(same_origin.len(), 2);
+        assert!(same_origin.iter().all(|request| request.contains("authorization: bearer secret")));
+        // Another port is another origin
+        let other_origin = lowercase(&other_log);
+        assert_eq!(other_origin.len(), 1);
+        assert!(!other_origin[0].contains("authorization"));
+        assert!(other_origin[0].contains("x-trace: 1"));
+    }
+
+    #[tokio::test]
+    async fn redirect_chains_stop_at_the_hop_limit() {
+        let (port, log) = serve("127.0.0.1", |request| {
+            let hop: u32 = path(request).trim_start_matches('/').parse().unwrap_or(0);
+            respond("302 Found", &format!("Location: /{}\r\n", hop + 1))
+        })
+        .await;
+        let result = run_stress_test(config(
+            &format!("http://127.0.0.1:{}/0", port),
+            serde_json::json!({ "redirect_policy": { "mode": "limited", "max_redirects": 3 } }),
+        ))
+        .await
+        .unwrap();
+
+        assert_eq!(log.lock().unwrap().len(), 4);
+        assert_eq!(result.status_codes, HashMap::from([("302".to_string(), 1)]));
+        assert_eq!(result.redirect_counts, HashMap::from([("3".to_string(), 1)]));
+        assert_eq!(result.redirect_hops.len(), 3);
+        assert_eq!(result.redirects_stopped, HashMap::from([("limit".to_string(), 1)]));
+    }
+
+    #[tokio::test]
+    async fn form_and_multipart_bodies_are_encoded() {
+        let file = std::env::temp_dir().join(format!("armandra-part-{}.txt", std::process::id()));
+        std::fs::write(&file, "file contents").unwrap();
+        let (port, log) = serve("127.0.0.1", ok).await;
+        let url = format!("http://127.0.0.1:{}/", port);
+
+        let form = serde_json::json!({
+            "method": "POST",
+            "body_source": { "type": "form", "fields": [["name", "a b"], ["q", "x&y"]] },
+        });
+        let multipart = serde_json::json!({
+            "method": "POST",
+            "body_source": { "type": "multipart", "parts": [
+                { "name": "note", "value": "hello" },
+                { "name": "upload", "file": file.to_str().unwrap(), "content_type": "text/plain" },
+            ] },
+        });
+        let form_result = run_stress_test(config(&url, form)).await;
+        let multipart_result = run_stress_test(config(&url, multipart)).await;
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(form_result.unwrap().successful_requests, 1);
+        assert_eq!(multipart_result.unwrap().successful_requests, 1);
+
+        let requests: Vec<String> = log.lock().unwrap().iter().map(|received| received.text.clone()).collect();
+        let form = requests[0].to_ascii_lowercase();
+        assert!(form.contains("content-type: application/x-www-form-urlencoded"));
+        assert!(form.ends_with("\r\n\r\nname=a+b&q=x%26y"));
+
+        let multipart = &requests[1];
+        let boundary = multipart
+            .lines()
+            .map(str::to_ascii_lowercase)
+            .find_map(|line| line.strip_prefix("content-type: multipart/form-data; boundary=").map(str::to_string))
+            .expect("multipart Content-Type with a boundary");
+        assert_eq!(multipart.matches(&format!("--{}", boundary)).count(), 3);
+        assert!(multipart.contains("name=\"note\"\r\n\r\nhello\r\n"));
+        let file_name = file.file_name().unwrap().to_str().unwrap();
+        assert!(multipart.contains(&format!("name=\"upload\"; filename=\"{}\"", file_name)));
+        assert!(multipart.to_ascii_lowercase().contains("content-type: text/plain"));
+        assert!(multipart.contains("\r\n\r\nfile contents\r\n"));
+    }
+
+    #[tokio::test]
+    async fn bodies_that_set_their_own_content_type_reject_one_in_headers() {
+        let bodies = [
+            serde_json::json!({ "type": "form", "fields": [["a", "1"]] }),
+            serde_json::json!({ "type": "multipart", "parts": [{ "name": "a", "value": "1" }] }),
+        ];
+        for body in bodies {
+            let extra = serde_json::json!({
+                "method": "POST",
+                "headers": { "content-type": "text/plain" },
+                "body_source": body,
+            });
+            let error = run_stress_test(config("http://127.0.0.1:9/", extra)).await.unwrap_err();
+            assert!(error.contains("Content-Type"), "{}", error);
+        }
+
+        let extra = serde_json::json!({
+            "method": "POST",
+            "body_source": { "type": "multipart", "parts": [{ "name": "a", "value": "1", "file": "a.txt" }] },
+        });
+        let error = run_stress_test(config("http://127.0.0.1:9/", extra)).await.unwrap_err();
+        assert!(error.contains("exactly one of value or file"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn a_breached_abort_condition_ends_the_run() {
+        let (port, _) = serve("127.0.0.1", |_: &str| respond("500 Internal Server Error", "")).await;
+        let (healthy_port, _) = serve("127.0.0.1", ok).await;
+        let extra = serde_json::json!({
+            "duration_ms": 2500,
+            "rate_per_second": 50.0,
+            "abort_conditions": { "max_error_rate": 50.0, "window_ms": 1000, "min_requests": 5 },
+        });
+
+        let result = run_stress_test(config(&format!("http://127.0.0.1:{}/", port), extra.clone())).await.unwrap();
+        let reason = result.aborted.expect("the run should be aborted");
+        assert!(reason.starts_with("Error rate 100.0%"), "{}", reason);
+        // Checked once a second, so the first or second check catches it
+        assert!(result.duration < 2200.0, "{}", result.duration);
+
+        let result = run_stress_test(config(&format!("http://127.0.0.1:{}/", healthy_port), extra)).await.unwrap();
+        assert_eq!(result.aborted, None);
+        assert!(result.total_requests >= 120, "{}", result.total_requests);
+    }
+
+    #[tokio::test]
+    async fn warmup_requests_are_kept_out_of_the_result() {
+        // The first five requests fail, so any that leak into the measured
+        // figures show up there
+        let served = AtomicUsize::new(0);
+        let (port, log) = serve("127.0.0.1", move |_: &str| match served.fetch_add(1, Ordering::Relaxed) {
+            0..=4 => respond("503 Service Unavailable", ""),
+            _ => ok(""),
+        })
+        .await;
+        let result = run_stress_test(config(
+            &format!("http://127.0.0.1:{}/", port),
+            serde_json::json!({ "requests_per_client": 10, "warmup": { "type": "requests", "count": 5 } }),
+        ))
+        .await
+        .unwrap();
+
+        assert_eq!(log.lock().unwrap().len(), 15);
+        assert_eq!(result.total_requests, 10);
+        assert_eq!(result.status_codes, HashMap::from([("200".to_string(), 10)]));
+        let warmup = result.warmup.expect("a warm-up result");
+        assert_eq!(warmup.total_requests, 5);
+        assert_eq!(warmup.status_codes, HashMap::from([("503".to_string(), 5)]));
+    }
+
+    #[tokio::test]
+    async fn think_times_are_validated() {
+        let invalid = [
+            serde_json::json!({ "type": "fixed", "ms": -1.0 }),
+            serde_json::json!({ "type": "uniform", "min_ms": 20.0, "max_ms": 10.0 }),
+            serde_json::json!({ "type": "uniform", "min_ms": -5.0, "max_ms": 10.0 }),
+            serde_json::json!({ "type": "normal", "mean_ms": 10.0, "std_dev_ms": -1.0 }),
+            serde_json::json!({ "type": "exponential", "mean_ms": -10.0 }),
+        ];
+        for think_time in invalid {
+            let extra = serde_json::json!({ "think_time": think_time });
+            assert!(run_stress_test(config("http://127.0.0.1:9/", extra)).await.is_err(), "{}", think_time);
+        }
+        assert!(ThinkTime::Uniform { min_ms: 0.0, max_ms: 0.0 }.validate().is_ok());
+    }
+
+    #[test]
+    fn sampled_think_times_stay_in_bounds() {
+        let ms = |think_time: &ThinkTime| think_time.sample().as_secs_f64() * 1000.0;
+        let uniform = ThinkTime::Uniform { min_ms: 10.0, max_ms: 20.0 };
+        let normal = ThinkTime::Normal { mean_ms: 5.0, std_dev_ms: 50.0 };
+        let exponential = ThinkTime::Exponential { mean_ms: 10.0 };
+        for _ in 0..1000 {
+            assert!((10.0..=20.0).contains(&ms(&uniform)));
+            // Negative draws pause for nothing rather than fail
+            assert!(ms(&normal) >= 0.0);
+            assert!(ms(&exponential) >= 0.0);
+        }
+        assert_eq!(ThinkTime::Fixed { ms: 7.0 }.sample(), Duration::from_millis(7));
+    }
+
+    #[tokio::test]
+    async fn virtual_users_think_between_requests() {
+        let (port, log) = serve("127.0.0.1", ok).await;
+        let result = run_stress_test(config(
+            &format!("http://127.0.0.1:{}/", port),
+            serde_json::json!({
+                "requests_per_client": 4,
+                "think_time": { "type": "uniform", "min_ms": 50.0, "max_ms": 80.0 },
+            }),
+        ))
+        .await
+        .unwrap();
+
+        assert_eq!(result.successful_requests, 4);
+        let arrivals: Vec<Instant> = log.lock().unwrap().iter().map(|received| received.at).collect();
+        for gap in arrivals.windows(2).map(|pair| pair[1].duration_since(pair[0])) {
+            // A request's own round trip adds a little on top of the pause
+            assert!(gap >= Duration::from_millis(50) && gap < Duration::from_millis(180), "{:?}", gap);
+        }
+        // No pause after the last request
+        assert!(result.duration < 80.0 * 3.0 + 150.0, "{}", result.duration);
+    }
 }
@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+
+use crate::stress_test::TestResult;
+
+// Pass/fail criteria written as `<metric> <op> <value>`, e.g. `p95 < 300ms`,
+// `error_rate < 1%` or `throughput > 500`. Latencies are in milliseconds
+// unless suffixed with `s`; error_rate is a percentage. A metric with nothing
+// to measure, such as a latency when no response came back, fails.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threshold {
+    metric: Metric,
+    op: Op,
+    limit: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Metric {
+    Min,
+    Avg,
+    Max,
+    P50,
+    P90,
+    P95,
+    P99,
+    ErrorRate,
+    Throughput,
+    Requests,
+    FailedRequests,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ThresholdOutcome {
+    pub expression: String,
+    // None when there was no data to measure
+    pub observed: Option<f64>,
+    pub limit: f64,
+    // "ms", "%", "req/s" or empty for counts
+    pub unit: String,
+    pub passed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Verdict {
+    pub passed: bool,
+    pub thresholds: Vec<ThresholdOutcome>,
+}
+
+impl Metric {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "min" => Some(Metric::Min),
+            "avg" => Some(Metric::Avg),
+            "max" => Some(Metric::Max),
+            "p50" | "median" => Some(Metric::P50),
+            "p90" => Some(Metric::P90),
+            "p95" => Some(Metric::P95),
+            "p99" => Some(Metric::P99),
+            "error_rate" => Some(Metric::ErrorRate),
+            "throughput" | "rps" => Some(Metric::Throughput),
+            "requests" => Some(Metric::Requests),
+            "failed_requests" | "errors" => Some(Metric::FailedRequests),
+            _ => None,
+        }
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            Metric::ErrorRate => "%",
+            Metric::Throughput => "req/s",
+            Metric::Requests | Metric::FailedRequests => "",
+            _ => "ms",
+        }
+    }
+
+    fn observe(&self, result: &TestResult) -> Option<f64> {
+        // Latencies read 0 rather than missing when no response came back
+        if self.unit() == "ms" && result.latency_histogram.count() == 0 {
+            return None;
+        }
+        match self {
+            Metric::Min => Some(result.min_response_time),
+            Metric::Avg => Some(result.avg_response_time),
+            Metric::Max => Some(result.max_response_time),
+            Metric::P50 => Some(result.p50_response_time),
+            Metric::P90 => Some(result.p90_response_time),
+            Metric::P95 => Some(result.p95_response_time),
+            Metric::P99 => Some(result.p99_response_time),
+            Metric::ErrorRate if result.total_requests == 0 => None,
+            Metric::ErrorRate => Some(result.failed_requests as f64 / result.total_requests as f64 * 100.0),
+            Metric::Throughput => Some(result.throughput),
+            Metric::Requests => Some(result.total_requests as f64),
+            Metric::FailedRequests => Some(result.failed_requests as f64),
+        }
+    }
+
+    // Converts the limit's suffix, if any, to the metric's unit
+    fn parse_limit(&self, value: &str) -> Option<f64> {
+        let (number, scale) = match self.unit() {
+            "ms" => match value.strip_suffix("ms") {
+                Some(number) => (number, 1.0),
+                None => match value.strip_suffix('s') {
+                    Some(number) => (number, 1000.0),
+                    None => (value, 1.0),
+                },
+            },
+            "%" => (value.strip_suffix('%').unwrap_or(value), 1.0),
+            "req/s" => (value.strip_suffix("/s").or_else(|| value.strip_suffix("rps")).unwrap_or(value), 1.0),
+            _ => (value, 1.0),
+        };
+        number.trim().parse::<f64>().ok().filter(|limit| limit.is_finite()).map(|limit| limit * scale)
+    }
+}
+
+impl Op {
+    fn holds(&self, observed: f64, limit: f64) -> bool {
+        match self {
+            Op::Lt => observed < limit,
+            Op::Le => observed <= limit,
+            Op::Gt => observed > limit,
+            Op::Ge => observed >= limit,
+        }
+    }
+}
+
+impl Threshold {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| format!("Invalid threshold \"{}\": {}", expression, reason);
+
+        // Two-character operators first so `<=` isn't read as `<`
+        let (position, op, len) = [("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)]
+            .iter()
+            .find_map(|(token, op)| expression.find(token).map(|position| (position, *op, token.len())))
+            .ok_or_else(|| invalid("expected <, <=, > or >="))?;
+
+        let name = expression[..position].trim().to_ascii_lowercase();
+        let metric = Metric::parse(&name).ok_or_else(|| invalid(&format!("unknown metric {}", name)))?;
+        let limit = metric
+            .parse_limit(expression[position + len..].trim())
+            .ok_or_else(|| invalid("expected a number"))?;
+
+        Ok(Threshold { metric, op, limit })
+    }
+
+    pub fn check(&self, expression: &str, result: &TestResult) -> ThresholdOutcome {
+        let observed = self.metric.observe(result);
+        ThresholdOutcome {
+            expression: expression.to_string(),
+            observed,
+            limit: self.limit,
+            unit: self.metric.unit().to_string(),
+            passed: observed.is_some_and(|observed| self.op.holds(observed, self.limit)),
+        }
+    }
+}
+
+// Parses every expression so a typo is caught before the test runs
+pub fn validate(expressions: &[String]) -> Result<(), String> {
+    expressions.iter().try_for_each(|expression| Threshold::parse(expression).map(|_| ()))
+}
+
+pub fn evaluate(expressions: &[String], result: &TestResult) -> Result<Verdict, String> {
+    let thresholds = expressions
+        .iter()
+        .map(|expression| Threshold::parse(expression).map(|threshold| threshold.check(expression, result)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Verdict {
+        passed: thresholds.iter().all(|threshold| threshold.passed),
+        thresholds,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result_with_latencies(latencies: &[f64], failed: u32) -> TestResult {
+        let mut result = TestResult::default();
+        for &latency in latencies {
+            result.latency_histogram.record(latency);
+            result.min_response_time = result.min_response_time.min(latency);
+            result.max_response_time = result.max_response_time.max(latency);
+            result.total_response_time += latency;
+        }
+        result.successful_requests = latencies.len() as u32;
+        result.failed_requests = failed;
+        result.total_requests = latencies.len() as u32 + failed;
+        result.finalize(1000.0);
+        result
+    }
+
+    #[test]
+    fn parses_units_and_operators() {
+        let parsed = |expression: &str| Threshold::parse(expression).unwrap();
+        assert_eq!(parsed("p95 < 300ms"), Threshold { metric: Metric::P95, op: Op::Lt, limit: 300.0 });
+        assert_eq!(parsed("P99<=1.5s"), Threshold { metric: Metric::P99, op: Op::Le, limit: 1500.0 });
+        assert_eq!(parsed("median < 20"), Threshold { metric: Metric::P50, op: Op::Lt, limit: 20.0 });
+        assert_eq!(parsed("error_rate < 1%"), Threshold { metric: Metric::ErrorRate, op: Op::Lt, limit: 1.0 });
+        assert_eq!(parsed("rps >= 500/s"), Threshold { metric: Metric::Throughput, op: Op::Ge, limit: 500.0 });
+        assert_eq!(parsed("errors > 0"), Threshold { metric: Metric::FailedRequests, op: Op::Gt, limit: 0.0 });
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for expression in ["p95 300ms", "p42 < 1", "p95 < fast", "p95 < ", "requests >= inf"] {
+            assert!(Threshold::parse(expression).is_err(), "{} parsed", expression);
+        }
+        assert!(validate(&["p95 < 1s".to_string(), "bogus".to_string()]).is_err());
+    }
+
+    #[test]
+    fn evaluates_against_the_result() {
+        let result = result_with_latencies(&[10.0, 20.0, 30.0, 40.0], 1);
+        let verdict = evaluate(&["max < 50ms".to_string(), "error_rate < 10%".to_string()], &result).unwrap();
+        assert!(verdict.thresholds[0].passed);
+        assert_eq!(verdict.thresholds[1].observed, Some(20.0));
+        assert!(!verdict.thresholds[1].passed);
+        assert!(!verdict.passed);
+    }
+
+    #[test]
+    fn latency_without_samples_fails() {
+        let result = result_with_latencies(&[], 5);
+        let expressions = ["p95 < 300ms", "avg < 300ms", "min < 300ms", "failed_requests >= 5"].map(String::from);
+        let verdict = evaluate(&expressions, &result).unwrap();
+
+        for latency in &verdict.thresholds[..3] {
+            assert_eq!(latency.observed, None, "{}", latency.expression);
+            assert!(!latency.passed, "{}", latency.expression);
+        }
+        assert!(verdict.thresholds[3].passed);
+        assert!(!verdict.passed);
+    }
+
+    #[test]
+    fn error_rate_without_requests_fails() {
+        let verdict = evaluate(&["error_rate < 1%".to_string()], &TestResult::default()).unwrap();
+        assert_eq!(verdict.thresholds[0].observed, None);
+        assert!(!verdict.passed);
+    }
+}
@@ -1,130 +1,762 @@
+use futures_util::stream::SplitStream;
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex, oneshot, broadcast};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex, Notify, oneshot, broadcast};
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream};
 use url::Url;
+use uuid::Uuid;
 
-use crate::stress_test::{TestConfig, TestResult};
+use crate::protocol::{
+    AbortRequest, AssignReply, AssignRequest, ClockSyncReply, ClockSyncRequest, ConfigureRequest, JoinRequest, MetricsDeltaReport, ResumeReply,
+    ResumeRequest, RoomEvent, RoomInfo, StartRequest, SubmitResultsRequest, TestStartInfo, WebSocketMessage, WorkerInfo, WorkerProfile,
+};
+use crate::stress_test::{run_stress_test_with_progress, AbortSignal, MetricsDelta, TestConfig, TestResult};
+use crate::thresholds;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RoomInfo {
-    pub room_id: String,
-    pub client_count: u32,
-    pub status: String,
-    pub config: Option<TestConfig>,
+// A request waiting for its response, keyed by request ID
+#[derive(Debug)]
+struct PendingRequest {
+    message_type: String,
+    sender: oneshot::Sender<serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TestStartInfo {
-    pub start_time: u64,
-    pub config: TestConfig,
+// What the coordinator handed out in its greeting; presented again after a
+// reconnect to take back our place
+#[derive(Debug, Clone)]
+struct Session {
+    client_id: String,
+    token: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct WebSocketMessage {
-    #[serde(rename = "type")]
-    message_type: String,
-    payload: serde_json::Value,
+// Estimate of the coordinator's clock relative to ours, in milliseconds:
+// coordinator time = local time + offset_ms
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ClockSync {
+    pub offset_ms: f64,
+    pub rtt_ms: f64,
+    pub uncertainty_ms: f64,
 }
 
-#[derive(Debug, Clone)]
-pub struct RoomStatusUpdate {
-    pub room_id: String,
-    pub status: String,
-    pub config: Option<TestConfig>,
+const CLOCK_SYNC_SAMPLES: usize = 8;
+// A start time further ahead than this is taken to be bogus
+const MAX_START_DELAY: Duration = Duration::from_secs(300);
+
+// How often the coordinator is pinged, and how many pongs may go missing
+// before the connection is considered dead and re-established
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    pub interval_ms: u64,
+    pub missed_pong_threshold: u32,
+}
+
+impl HeartbeatConfig {
+    // A threshold of 0 would give up on the connection before the first ping
+    pub fn validate(&self) -> Result<(), String> {
+        if self.missed_pong_threshold == 0 {
+            return Err("Heartbeat missed_pong_threshold must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig {
+            interval_ms: 5000,
+            missed_pong_threshold: 3,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ConnectionHealth {
+    pub connected: bool,
+    // Unix time in milliseconds of the last frame from the coordinator
+    pub last_seen: Option<u64>,
+    pub rtt_ms: Option<f64>,
+    pub missed_pongs: u32,
+}
+
+#[derive(Debug, Default)]
+struct HeartbeatState {
+    health: ConnectionHealth,
+    sequence: u64,
+    // Set while a ping is waiting for its pong
+    ping_sent_at: Option<Instant>,
+}
+
+impl HeartbeatState {
+    fn next_ping(&mut self, missed_pong_threshold: u32) -> Option<u64> {
+        if self.ping_sent_at.is_some() {
+            self.health.missed_pongs += 1;
+        }
+        if self.health.missed_pongs >= missed_pong_threshold {
+            return None;
+        }
+
+        self.sequence += 1;
+        self.ping_sent_at = Some(Instant::now());
+        Some(self.sequence)
+    }
+
+    // False for a pong that doesn't answer the latest ping
+    fn record_pong(&mut self, data: &[u8]) -> bool {
+        let sequence = <[u8; 8]>::try_from(data).ok().map(u64::from_be_bytes);
+
+        // Pongs for pings we've already given up on don't count
+        if sequence != Some(self.sequence) {
+            return false;
+        }
+        if let Some(sent_at) = self.ping_sent_at.take() {
+            self.health.rtt_ms = Some(sent_at.elapsed().as_secs_f64() * 1000.0);
+            self.health.missed_pongs = 0;
+        }
+        true
+    }
+}
+
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+fn now_ms() -> f64 {
+    chrono::Utc::now().timestamp_micros() as f64 / 1000.0
+}
+
+fn to_payload<T: Serialize>(value: &T) -> Result<serde_json::Value, String> {
+    serde_json::to_value(value).map_err(|e| format!("Failed to serialize message: {}", e))
+}
+
+// Progress of a test this client runs on behalf of the room
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "type", content = "payload", rename_all = "kebab-case")]
+pub enum WorkerEvent {
+    Running(TestConfig),
+    Finished(TestResult),
+    Failed(String),
 }
 
 #[derive(Debug, Clone)]
 pub struct WebSocketClient {
     tx: mpsc::Sender<Message>,
     connected: Arc<Mutex<bool>>,
-    response_channels: Arc<Mutex<HashMap<String, oneshot::Sender<serde_json::Value>>>>,
-    status_tx: broadcast::Sender<RoomStatusUpdate>,
+    response_channels: Arc<Mutex<HashMap<String, PendingRequest>>>,
+    status_tx: broadcast::Sender<RoomEvent>,
+    client_id: Arc<Mutex<Option<String>>>,
+    // None when the coordinator doesn't support resuming sessions
+    session: Arc<Mutex<Option<Session>>>,
+    worker_tx: broadcast::Sender<WorkerEvent>,
+    auto_run: Arc<AtomicBool>,
+    // Relative weight the coordinator uses when splitting a load target
+    capacity: Arc<AtomicU32>,
+    profile: Arc<Mutex<WorkerProfile>>,
+    running: Arc<AtomicBool>,
+    clock: Arc<Mutex<Option<ClockSync>>>,
+    // The room to rejoin after a reconnect
+    room_id: Arc<Mutex<Option<String>>>,
+    // Stops the room test this worker is running, if any
+    current_abort: Arc<Mutex<Option<AbortSignal>>>,
+    // Thresholds of the room's current test, checked against the aggregate
+    room_thresholds: Arc<Mutex<Vec<String>>>,
+    // Registered as an idle worker; registered again after a reconnect
+    registered: Arc<AtomicBool>,
+    // Results submitted while offline, resent once we're back in the room
+    pending_results: Arc<Mutex<Vec<TestResult>>>,
+    closing: Arc<AtomicBool>,
+    shutdown: Arc<Notify>,
+    heartbeat_config: HeartbeatConfig,
+    heartbeat: Arc<Mutex<HeartbeatState>>,
+    health_tx: broadcast::Sender<ConnectionHealth>,
 }
 
 impl WebSocketClient {
-    pub async fn new(server_url: &str) -> Result<Self, String> {
+    pub async fn new(server_url: &str, heartbeat_config: HeartbeatConfig) -> Result<Self, String> {
+        heartbeat_config.validate()?;
         let url = Url::parse(server_url).map_err(|e| format!("Invalid WebSocket URL: {}", e))?;
 
-        let (ws_stream, _) = connect_async(url)
+        let (ws_stream, _) = connect_async(url.clone())
             .await
             .map_err(|e| format!("Failed to connect to WebSocket server: {}", e))?;
 
-        let (mut write, mut read) = ws_stream.split();
+        let (tx, rx) = mpsc::channel::<Message>(100);
+
+        // Create a broadcast channel for room events
+        let (status_tx, _) = broadcast::channel::<RoomEvent>(100);
+        let (worker_tx, _) = broadcast::channel::<WorkerEvent>(16);
+        let (health_tx, _) = broadcast::channel::<ConnectionHealth>(16);
 
-        let (tx, mut rx) = mpsc::channel::<Message>(100);
-        let connected = Arc::new(Mutex::new(true));
-        let response_channels = Arc::new(Mutex::new(HashMap::<String, oneshot::Sender<serde_json::Value>>::new()));
+        // test-started broadcasts are handed to the auto-run task; it stops
+        // once the connection task exits and drops the sender
+        let (start_tx, mut start_rx) = mpsc::channel::<TestStartInfo>(8);
 
-        // Create a broadcast channel for room status updates
-        let (status_tx, _) = broadcast::channel::<RoomStatusUpdate>(100);
+        let client = Self {
+            tx,
+            connected: Arc::new(Mutex::new(true)),
+            response_channels: Arc::new(Mutex::new(HashMap::new())),
+            status_tx,
+            client_id: Arc::new(Mutex::new(None)),
+            session: Arc::new(Mutex::new(None)),
+            worker_tx,
+            auto_run: Arc::new(AtomicBool::new(true)),
+            capacity: Arc::new(AtomicU32::new(1)),
+            profile: Arc::new(Mutex::new(WorkerProfile::default())),
+            running: Arc::new(AtomicBool::new(false)),
+            clock: Arc::new(Mutex::new(None)),
+            room_id: Arc::new(Mutex::new(None)),
+            current_abort: Arc::new(Mutex::new(None)),
+            room_thresholds: Arc::new(Mutex::new(Vec::new())),
+            registered: Arc::new(AtomicBool::new(false)),
+            pending_results: Arc::new(Mutex::new(Vec::new())),
+            closing: Arc::new(AtomicBool::new(false)),
+            shutdown: Arc::new(Notify::new()),
+            heartbeat_config,
+            heartbeat: Arc::new(Mutex::new(HeartbeatState::default())),
+            health_tx,
+        };
 
-        let connected_clone = connected.clone();
-        let response_channels_clone = response_channels.clone();
-        let status_tx_clone = status_tx.clone();
+        tokio::spawn(client.clone().maintain_connection(url, ws_stream, rx, start_tx));
 
-        // Handle outgoing messages
+        // Run the room's test automatically whenever the host starts it
+        let runner = client.clone();
         tokio::spawn(async move {
-            while let Some(message) = rx.recv().await {
-                if write.send(message).await.is_err() {
-                    let mut connected = connected_clone.lock().await;
-                    *connected = false;
-                    break;
+            while let Some(start) = start_rx.recv().await {
+                // Whatever isn't run is declined, so the room doesn't wait on us
+                let skipped = if !runner.auto_run.load(Ordering::SeqCst) {
+                    Some("Auto-run is off")
+                } else if runner.running.swap(true, Ordering::SeqCst) {
+                    log::info!("Ignoring test-started, a test is already running");
+                    Some("A test is already running")
+                } else {
+                    None
+                };
+                if let Some(reason) = skipped {
+                    let decliner = runner.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = decliner.decline_test(reason).await {
+                            log::warn!("Failed to decline the test: {}", e);
+                        }
+                    });
+                    continue;
                 }
+                let worker = runner.clone();
+                tokio::spawn(async move {
+                    worker.run_room_test(start).await;
+                    worker.running.store(false, Ordering::SeqCst);
+                });
             }
         });
 
-        // Handle incoming messages
-        let connected_clone = connected.clone();
-        tokio::spawn(async move {
-            while let Some(message_result) = read.next().await {
-                match message_result {
-                    Ok(Message::Text(text)) => {
-                        println!("Received message: {}", text);
-                        if let Ok(ws_message) = serde_json::from_str::<WebSocketMessage>(&text) {
-                            println!("Parsed message type: {}", ws_message.message_type);
-                            let mut channels = response_channels_clone.lock().await;
-
-                            // Check for response types
-                            if ws_message.message_type.ends_with("-response") {
-                                let request_type = ws_message.message_type.replace("-response", "");
-                                if let Some(sender) = channels.remove(&request_type) {
-                                    println!("Sending response for: {}", request_type);
-                                    let _ = sender.send(ws_message.payload);
-                                }
+        Ok(client)
+    }
+
+    // Owns the socket: writes outgoing messages, and when the connection drops
+    // reconnects with backoff and restores the room session. Runs until
+    // `disconnect` is called.
+    async fn maintain_connection(
+        self,
+        url: Url,
+        mut ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+        mut rx: mpsc::Receiver<Message>,
+        start_tx: mpsc::Sender<TestStartInfo>,
+    ) {
+        loop {
+            let (mut write, read) = ws_stream.split();
+            *self.heartbeat.lock().await = HeartbeatState::default();
+            let mut heartbeat = tokio::time::interval(Duration::from_millis(self.heartbeat_config.interval_ms.max(1)));
+
+            // Handle incoming messages
+            let reader = self.clone();
+            let reader_start_tx = start_tx.clone();
+            let mut read_task = tokio::spawn(async move {
+                reader.read_messages(read, reader_start_tx).await;
+            });
+
+            // Handle outgoing messages
+            loop {
+                tokio::select! {
+                    message = rx.recv() => match message {
+                        Some(message) => {
+                            if write.send(message).await.is_err() {
+                                break;
                             }
                         }
+                        None => {
+                            self.closing.store(true, Ordering::SeqCst);
+                            break;
+                        }
                     },
-                    Ok(Message::Close(_)) => {
-                        println!("WebSocket connection closed");
-                        let mut connected = connected_clone.lock().await;
-                        *connected = false;
-                        break;
+                    _ = &mut read_task => break,
+                    _ = heartbeat.tick() => match self.next_ping().await {
+                        Some(sequence) => {
+                            if write.send(Message::Ping(sequence.to_be_bytes().to_vec())).await.is_err() {
+                                break;
+                            }
+                        }
+                        None => {
+                            log::warn!("Coordinator stopped answering pings, reconnecting");
+                            break;
+                        }
                     },
-                    Err(e) => {
-                        println!("WebSocket error: {:?}", e);
-                        let mut connected = connected_clone.lock().await;
-                        *connected = false;
+                    _ = self.shutdown.notified() => {
+                        let _ = write.send(Message::Close(None)).await;
                         break;
-                    },
-                    _ => {}
+                    }
+                }
+            }
+            read_task.abort();
+
+            *self.connected.lock().await = false;
+            // Fail outstanding requests now rather than after their timeout
+            self.response_channels.lock().await.clear();
+
+            self.publish_health().await;
+
+            if self.closing.load(Ordering::SeqCst) {
+                break;
+            }
+            let _ = self.status_tx.send(RoomEvent::Disconnected);
+
+            // The new connection's greeting replaces the session
+            let previous = self.session.lock().await.clone();
+            ws_stream = match self.reconnect(&url).await {
+                Some(ws_stream) => ws_stream,
+                None => break,
+            };
+            *self.connected.lock().await = true;
+
+            let client = self.clone();
+            tokio::spawn(async move { client.restore_session(previous).await });
+        }
+    }
+
+    async fn read_messages(
+        &self,
+        mut read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+        start_tx: mpsc::Sender<TestStartInfo>,
+    ) {
+        while let Some(message_result) = read.next().await {
+            if message_result.is_ok() {
+                self.heartbeat.lock().await.health.last_seen = Some(chrono::Utc::now().timestamp_millis() as u64);
+            }
+
+            match message_result {
+                Ok(Message::Pong(data)) => {
+                    self.record_pong(&data).await;
+                },
+                Ok(Message::Text(text)) => {
+                    log::debug!("Received message: {}", text);
+                    if let Ok(ws_message) = serde_json::from_str::<WebSocketMessage>(&text) {
+                        log::debug!("Parsed message type: {}", ws_message.message_type);
+
+                        // Responses are matched to their request by ID only
+                        if let Some(request_id) = &ws_message.request_id {
+                            let pending = self.response_channels.lock().await.remove(request_id);
+                            match pending {
+                                Some(pending) => {
+                                    let expected = format!("{}-response", pending.message_type);
+                                    if ws_message.message_type != expected && ws_message.message_type != "error" {
+                                        log::warn!(
+                                            "Response to {} request {} has type {}, expected {}",
+                                            pending.message_type, request_id, ws_message.message_type, expected
+                                        );
+                                    }
+                                    log::debug!("Sending response for: {} ({})", pending.message_type, request_id);
+                                    let _ = pending.sender.send(ws_message.payload);
+                                }
+                                None => log::warn!(
+                                    "Dropping orphaned {} for request {}; it may have timed out",
+                                    ws_message.message_type, request_id
+                                ),
+                            }
+                        } else if ws_message.message_type.ends_with("-response") {
+                            log::warn!("Dropping {} without a request ID", ws_message.message_type);
+                        } else {
+                            match serde_json::from_str::<RoomEvent>(&text) {
+                                // No receivers just means nobody is listening yet
+                                Ok(mut event) => {
+                                    match &mut event {
+                                        RoomEvent::Connected { client_id, session_token } => {
+                                            *self.client_id.lock().await = Some(client_id.clone());
+                                            // Kept to ourselves rather than passed on to subscribers
+                                            *self.session.lock().await = session_token.take().map(|token| Session {
+                                                client_id: client_id.clone(),
+                                                token,
+                                            });
+                                        }
+                                        RoomEvent::TestConfigured(config) => {
+                                            *self.room_thresholds.lock().await = config.thresholds.clone();
+                                        }
+                                        RoomEvent::TestAborted { reason, .. } => {
+                                            if let Some(abort) = &*self.current_abort.lock().await {
+                                                abort.abort(reason.clone());
+                                            }
+                                        }
+                                        RoomEvent::TestStarted(start) => {
+                                            *self.room_thresholds.lock().await = start.config.thresholds.clone();
+                                            let _ = start_tx.try_send(start.clone());
+                                        }
+                                        RoomEvent::LiveMetrics(metrics) => {
+                                            metrics.p50_response_time = metrics.latency_histogram.percentile(50.0);
+                                            metrics.p95_response_time = metrics.latency_histogram.percentile(95.0);
+                                            metrics.p99_response_time = metrics.latency_histogram.percentile(99.0);
+                                        }
+                                        // The JavaScript coordinator merges the histograms but
+                                        // leaves the percentiles to us
+                                        RoomEvent::TestCompleted(aggregated) => {
+                                            aggregated.result.fill_percentiles();
+                                            self.judge(&mut aggregated.result).await;
+                                        }
+                                        _ => {}
+                                    }
+                                    let _ = self.status_tx.send(event);
+                                }
+                                Err(e) => log::debug!("Ignoring message {}: {}", ws_message.message_type, e),
+                            }
+                        }
+                    }
+                },
+                Ok(Message::Close(_)) => {
+                    log::info!("WebSocket connection closed");
+                    break;
+                },
+                Err(e) => {
+                    log::warn!("WebSocket error: {:?}", e);
+                    break;
+                },
+                _ => {}
+            }
+        }
+    }
+
+    // Returns the sequence number for the next ping, or None once too many
+    // pongs have gone missing
+    async fn next_ping(&self) -> Option<u64> {
+        self.heartbeat.lock().await.next_ping(self.heartbeat_config.missed_pong_threshold)
+    }
+
+    async fn record_pong(&self, data: &[u8]) {
+        if self.heartbeat.lock().await.record_pong(data) {
+            self.publish_health().await;
+        }
+    }
+
+    pub async fn health(&self) -> ConnectionHealth {
+        let mut health = self.heartbeat.lock().await.health.clone();
+        health.connected = self.is_connected().await;
+        health
+    }
+
+    pub fn subscribe_health(&self) -> broadcast::Receiver<ConnectionHealth> {
+        self.health_tx.subscribe()
+    }
+
+    async fn publish_health(&self) {
+        let _ = self.health_tx.send(self.health().await);
+    }
+
+    // Exponential backoff with full jitter, so a coordinator restart isn't
+    // met by every client reconnecting at the same instant
+    async fn reconnect(&self, url: &Url) -> Option<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+        let mut delay = RECONNECT_INITIAL_DELAY;
+
+        loop {
+            let wait = delay.mul_f64(rand::random::<f64>());
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.shutdown.notified() => return None,
+            }
+            if self.closing.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            log::info!("Reconnecting to {}", url);
+            match connect_async(url.clone()).await {
+                Ok((ws_stream, _)) => return Some(ws_stream),
+                Err(e) => log::warn!("Reconnect failed: {}", e),
+            }
+
+            delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+        }
+    }
+
+    // Resumes the session we had, or failing that rejoins the room we were
+    // in, then resends results queued while offline
+    async fn restore_session(&self, previous: Option<Session>) {
+        let room_id = self.room_id.lock().await.clone();
+        let _ = self.status_tx.send(RoomEvent::Reconnected { room_id: room_id.clone() });
+
+        // The room we're back in, if the session was resumed
+        let resumed = match previous {
+            Some(session) => match self.resume_session(session).await {
+                Ok(room) => Some(room),
+                Err(e) => {
+                    log::info!("Could not resume the previous session: {}", e);
+                    None
                 }
+            },
+            None => None,
+        };
+
+        if resumed.is_some() {
+            log::info!("Resumed session");
+        } else if let Some(room_id) = room_id {
+            if let Err(e) = self.join_room(&room_id).await {
+                log::warn!("Failed to rejoin room {}: {}", room_id, e);
+                return;
+            }
+            log::info!("Rejoined room {}", room_id);
+        } else if self.registered.load(Ordering::SeqCst) {
+            if let Err(e) = self.register_worker().await {
+                log::warn!("Failed to register as a worker again: {}", e);
+                return;
+            }
+        }
+
+        let queued = std::mem::take(&mut *self.pending_results.lock().await);
+        let resending = !queued.is_empty();
+        for results in queued {
+            if let Err(e) = self.submit_results(&results).await {
+                log::warn!("Failed to resend results {}: {}", results.test_id, e);
             }
+        }
+
+        // A test started while we were away never reached us
+        let missed_start = matches!(resumed, Some(Some(room)) if room.status == "running");
+        if missed_start && !resending && !self.running.load(Ordering::SeqCst) {
+            if let Err(e) = self.decline_test("Missed the test start while reconnecting").await {
+                log::warn!("Failed to decline the test: {}", e);
+            }
+        }
+    }
+
+    async fn resume_session(&self, session: Session) -> Result<Option<RoomInfo>, String> {
+        let request = ResumeRequest {
+            client_id: session.client_id.clone(),
+            session_token: session.token.clone(),
+        };
+        let response = self.send_message_and_wait_for_response("resume-session", to_payload(&request)?).await?;
+        let reply: ResumeReply =
+            serde_json::from_value(response).map_err(|e| format!("Invalid resume-session response: {}", e))?;
+
+        *self.room_id.lock().await = reply.room.as_ref().map(|room| room.room_id.clone());
+        *self.client_id.lock().await = Some(session.client_id.clone());
+        let _ = self.status_tx.send(RoomEvent::Connected {
+            client_id: session.client_id.clone(),
+            session_token: None,
         });
+        *self.session.lock().await = Some(session);
+        Ok(reply.room)
+    }
 
-        Ok(Self {
-            tx,
-            connected,
-            response_channels,
-        })
+    // Closes the connection for good; no reconnect is attempted
+    pub fn disconnect(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+        self.shutdown.notify_one();
+    }
+
+    pub fn subscribe_worker(&self) -> broadcast::Receiver<WorkerEvent> {
+        self.worker_tx.subscribe()
+    }
+
+    // Whether test-started broadcasts run the test and submit results without
+    // the user having to do it by hand. On by default.
+    pub fn set_auto_run(&self, enabled: bool) {
+        self.auto_run.store(enabled, Ordering::SeqCst);
+    }
+
+    // Share of a room-wide load target this worker should take, relative to
+    // the other workers' capacities. Sent when creating or joining a room.
+    pub fn set_capacity(&self, capacity: u32) {
+        self.capacity.store(capacity, Ordering::SeqCst);
+    }
+
+    // Label and concurrency hint reported alongside the detected machine
+    // details. Takes effect the next time we create or join a room.
+    pub async fn set_worker_profile(&self, profile: WorkerProfile) {
+        *self.profile.lock().await = profile;
+    }
+
+    // NTP-style exchange with the coordinator. The sample with the lowest
+    // round trip wins, since it bounds the error most tightly.
+    pub async fn sync_clock(&self) -> Result<ClockSync, String> {
+        let mut best: Option<ClockSync> = None;
+
+        for _ in 0..CLOCK_SYNC_SAMPLES {
+            let client_send_time = now_ms();
+            let response = self.send_message_and_wait_for_response(
+                "clock-sync",
+                to_payload(&ClockSyncRequest { client_send_time })?
+            ).await?;
+            let client_receive_time = now_ms();
+
+            let reply: ClockSyncReply = serde_json::from_value(response)
+                .map_err(|e| format!("Invalid clock-sync response: {}", e))?;
+            let server_receive_time = reply.server_receive_time;
+            let server_send_time = reply.server_send_time;
+
+            let rtt_ms = (client_receive_time - client_send_time) - (server_send_time - server_receive_time);
+            let offset_ms = ((server_receive_time - client_send_time) + (server_send_time - client_receive_time)) / 2.0;
+            let sample = ClockSync {
+                offset_ms,
+                rtt_ms,
+                uncertainty_ms: rtt_ms.max(0.0) / 2.0,
+            };
+
+            best = match best {
+                Some(best) if best.rtt_ms <= sample.rtt_ms => Some(best),
+                _ => Some(sample),
+            };
+        }
+
+        let best = best.ok_or_else(|| "No clock samples collected".to_string())?;
+        log::info!("Clock offset {:.2}ms ± {:.2}ms", best.offset_ms, best.uncertainty_ms);
+        *self.clock.lock().await = Some(best);
+        Ok(best)
+    }
+
+    pub async fn clock(&self) -> Option<ClockSync> {
+        *self.clock.lock().await
+    }
+
+    // Sleeps until the coordinator's scheduled start time, translated to our
+    // clock. An abort cuts the wait short; the run that follows then ends
+    // straight away and reports it.
+    async fn wait_for_start(&self, start_time: u64, abort: &AbortSignal) -> Result<(), String> {
+        let offset = self.clock().await.map(|clock| clock.offset_ms).unwrap_or(0.0);
+        let wait_ms = start_time as f64 - offset - now_ms();
+        if wait_ms <= 0.0 {
+            return Ok(());
+        }
+
+        let wait = Duration::from_secs_f64(wait_ms / 1000.0);
+        if wait > MAX_START_DELAY {
+            return Err(format!(
+                "The start time is {:.0}s away, more than the {}s allowed",
+                wait.as_secs_f64(),
+                MAX_START_DELAY.as_secs()
+            ));
+        }
+        let mut aborted = abort.subscribe();
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = aborted.wait_for(|reason| reason.is_some()) => {}
+        }
+        Ok(())
+    }
+
+    async fn run_room_test(&self, start: TestStartInfo) {
+        let _ = self.worker_tx.send(WorkerEvent::Running(start.config.clone()));
+
+        // When this worker's abort conditions trip, the rest of the room stops
+        // too. An abort received from the room is echoed back, which the
+        // coordinator ignores.
+        let abort = AbortSignal::new();
+        *self.current_abort.lock().await = Some(abort.clone());
+        let notifier = self.clone();
+        let mut tripped = abort.subscribe();
+        let abort_task = tokio::spawn(async move {
+            if tripped.changed().await.is_err() {
+                return;
+            }
+            let reason = tripped.borrow().clone();
+            if let Some(reason) = reason {
+                if let Err(e) = notifier.abort_test(&reason).await {
+                    log::warn!("Failed to tell the room about the abort: {}", e);
+                }
+            }
+        });
+
+        if let Err(e) = self.wait_for_start(start.start_time, &abort).await {
+            abort_task.abort();
+            *self.current_abort.lock().await = None;
+            log::warn!("Not running the test: {}", e);
+            if let Err(decline_error) = self.decline_test(&e).await {
+                log::warn!("Failed to decline the test: {}", decline_error);
+            }
+            let _ = self.worker_tx.send(WorkerEvent::Failed(e));
+            return;
+        }
+        let clock = self.clock().await;
+
+        // Stream interim metrics to the coordinator while the test runs
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel::<MetricsDelta>();
+        let streamer = self.clone();
+        let stream_task = tokio::spawn(async move {
+            while let Some(delta) = progress_rx.recv().await {
+                if let Err(e) = streamer.report_delta(delta).await {
+                    log::warn!("Failed to send metrics delta: {}", e);
+                }
+            }
+        });
+
+        let outcome = run_stress_test_with_progress(start.config, Some(progress_tx), abort).await;
+        let _ = stream_task.await;
+        abort_task.abort();
+        *self.current_abort.lock().await = None;
+
+        match outcome {
+            Ok(mut result) => {
+                result.clock_offset = clock.map(|clock| clock.offset_ms);
+                result.clock_uncertainty = clock.map(|clock| clock.uncertainty_ms);
+
+                let _ = self.worker_tx.send(WorkerEvent::Finished(result.clone()));
+                if let Err(e) = self.submit_results(&result).await {
+                    log::warn!("Failed to submit results: {}", e);
+                    let _ = self.worker_tx.send(WorkerEvent::Failed(format!("Failed to submit results: {}", e)));
+                }
+            }
+            Err(e) => {
+                log::warn!("Test failed: {}", e);
+                if let Err(decline_error) = self.decline_test(&format!("Test failed: {}", e)).await {
+                    log::warn!("Failed to decline the test: {}", decline_error);
+                }
+                let _ = self.worker_tx.send(WorkerEvent::Failed(e));
+            }
+        }
+    }
+
+    async fn report_delta(&self, delta: MetricsDelta) -> Result<(), String> {
+        self.send_message("metrics-delta", to_payload(&MetricsDeltaReport { delta })?).await
+    }
+
+    // The ID the coordinator assigned us in its `connected` greeting
+    pub async fn client_id(&self) -> Option<String> {
+        self.client_id.lock().await.clone()
     }
 
     pub async fn is_connected(&self) -> bool {
         *self.connected.lock().await
     }
 
+    pub fn subscribe(&self) -> broadcast::Receiver<RoomEvent> {
+        self.status_tx.subscribe()
+    }
+
+    // Fire-and-forget message; the coordinator sends no response
+    async fn send_message(&self, message_type: &str, payload: serde_json::Value) -> Result<(), String> {
+        if !self.is_connected().await {
+            return Err("WebSocket is not connected".to_string());
+        }
+
+        let message = WebSocketMessage {
+            message_type: message_type.to_string(),
+            payload,
+            request_id: None,
+        };
+        let message_str = serde_json::to_string(&message)
+            .map_err(|e| format!("Failed to serialize message: {}", e))?;
+
+        self.tx
+            .send(Message::Text(message_str))
+            .await
+            .map_err(|e| format!("Failed to send message: {}", e))
+    }
+
     async fn send_message_and_wait_for_response(
         &self,
         message_type: &str,
@@ -136,36 +768,50 @@ impl WebSocketClient {
 
         // Create a channel to receive the response
         let (tx, rx) = oneshot::channel();
+        let request_id = Uuid::new_v4().to_string();
 
-        // Store the channel in the map using the message type as the key
+        // Store the channel in the map using the request ID as the key
         {
             let mut channels = self.response_channels.lock().await;
-            channels.insert(message_type.to_string(), tx);
+            channels.insert(request_id.clone(), PendingRequest {
+                message_type: message_type.to_string(),
+                sender: tx,
+            });
         }
 
         // Create the message
-        let message = serde_json::json!({
-            "type": message_type,
-            "payload": payload
-        });
+        let message = WebSocketMessage {
+            message_type: message_type.to_string(),
+            payload,
+            request_id: Some(request_id.clone()),
+        };
 
-        println!("Sending message: {}", message_type);
+        log::debug!("Sending message: {} ({})", message_type, request_id);
 
         // Serialize and send the message
-        let message_str = serde_json::to_string(&message)
-            .map_err(|e| format!("Failed to serialize message: {}", e))?;
-
-        self.tx
-            .send(Message::Text(message_str))
-            .await
-            .map_err(|e| format!("Failed to send message: {}", e))?;
+        let sent = match serde_json::to_string(&message) {
+            Ok(message_str) => self.tx
+                .send(Message::Text(message_str))
+                .await
+                .map_err(|e| format!("Failed to send message: {}", e)),
+            Err(e) => Err(format!("Failed to serialize message: {}", e)),
+        };
+        if let Err(e) = sent {
+            self.response_channels.lock().await.remove(&request_id);
+            return Err(e);
+        }
 
-        println!("Waiting for response to: {}", message_type);
+        log::debug!("Waiting for response to: {}", message_type);
 
         // Wait for response with timeout
-        match tokio::time::timeout(std::time::Duration::from_secs(10), rx).await {
+        let response = tokio::time::timeout(std::time::Duration::from_secs(10), rx).await;
+
+        // Forget the request so a late response is reported as orphaned
+        self.response_channels.lock().await.remove(&request_id);
+
+        match response {
             Ok(Ok(response)) => {
-                println!("Received response for: {}", message_type);
+                log::debug!("Received response for: {}", message_type);
 
                 // Check if the response contains a success field
                 let success = response.get("success")
@@ -177,114 +823,181 @@ impl WebSocketClient {
                         .and_then(|v| v.as_str())
                         .unwrap_or("Unknown error");
 
-                    println!("Error in response: {}", error);
+                    log::warn!("Error in response: {}", error);
                     return Err(error.to_string());
                 }
 
                 Ok(response)
             },
             Ok(Err(_)) => {
-                println!("Response channel closed for: {}", message_type);
+                log::debug!("Response channel closed for: {}", message_type);
                 Err("Response channel closed".to_string())
             },
             Err(_) => {
-                println!("Timeout waiting for response to: {}", message_type);
+                log::warn!("Timeout waiting for response to: {}", message_type);
                 Err("Timeout waiting for response".to_string())
             },
         }
     }
 
-    pub async fn create_room(&self) -> Result<String, String> {
+    pub async fn create_room(&self) -> Result<RoomInfo, String> {
+        self.enter_room("create-room", None).await
+    }
+
+    pub async fn join_room(&self, room_id: &str) -> Result<RoomInfo, String> {
+        self.enter_room("join-room", Some(room_id)).await
+    }
+
+    async fn enter_room(&self, message_type: &str, room_id: Option<&str>) -> Result<RoomInfo, String> {
+        let request = JoinRequest {
+            room_id: room_id.map(str::to_string),
+            capacity: self.capacity.load(Ordering::SeqCst),
+            worker: Some(WorkerInfo::detect(&*self.profile.lock().await)),
+        };
         let response = self.send_message_and_wait_for_response(
-            "create-room",
-            serde_json::json!({})
+            message_type,
+            to_payload(&request)?
         ).await?;
 
         // Success check is already done in send_message_and_wait_for_response
 
-        let room_id = response.get("roomId")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| "No room ID in response".to_string())?;
+        let room: RoomInfo = serde_json::from_value(response)
+            .map_err(|e| format!("Invalid {} response: {}", message_type, e))?;
 
-        Ok(room_id.to_string())
+        *self.room_id.lock().await = Some(room.room_id.clone());
+        self.registered.store(false, Ordering::SeqCst);
+        self.refresh_clock().await;
+
+        Ok(room)
     }
 
-    pub async fn join_room(&self, room_id: &str) -> Result<RoomInfo, String> {
-        let response = self.send_message_and_wait_for_response(
-            "join-room",
-            serde_json::json!({ "roomId": room_id })
+    // Leaves any room and waits as an idle worker until a host assigns us to
+    // theirs, which arrives as RoomEvent::RoomAssigned
+    pub async fn register_worker(&self) -> Result<(), String> {
+        let request = JoinRequest {
+            room_id: None,
+            capacity: self.capacity.load(Ordering::SeqCst),
+            worker: Some(WorkerInfo::detect(&*self.profile.lock().await)),
+        };
+        self.send_message_and_wait_for_response(
+            "register-worker",
+            to_payload(&request)?
         ).await?;
 
-        // Success check is already done in send_message_and_wait_for_response
-
-        let room_id = response.get("roomId")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| "No room ID in response".to_string())?;
+        *self.room_id.lock().await = None;
+        self.registered.store(true, Ordering::SeqCst);
+        Ok(())
+    }
 
-        let client_count = response.get("clientCount")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(1) as u32;
+    // Pulls up to `count` idle workers (all of them when None) into the room
+    // we host. Returns the client IDs that were sent an assignment.
+    pub async fn assign_workers(&self, count: Option<u32>) -> Result<Vec<String>, String> {
+        let response = self.send_message_and_wait_for_response(
+            "assign-workers",
+            to_payload(&AssignRequest { count })?
+        ).await?;
 
-        let status = response.get("status")
-            .and_then(|v| v.as_str())
-            .unwrap_or("waiting");
+        let reply: AssignReply = serde_json::from_value(response)
+            .map_err(|e| format!("Invalid assign-workers response: {}", e))?;
+        Ok(reply.assigned)
+    }
 
-        let config = response.get("config")
-            .and_then(|v| {
-                if v.is_null() {
-                    None
-                } else {
-                    serde_json::from_value(v.clone()).ok()
-                }
-            });
+    // Neither coordinator knows about thresholds, so the verdict on the
+    // room's combined result is worked out here
+    async fn judge(&self, result: &mut TestResult) {
+        let thresholds = self.room_thresholds.lock().await;
+        if thresholds.is_empty() {
+            return;
+        }
+        match thresholds::evaluate(&thresholds, result) {
+            Ok(verdict) => result.verdict = Some(verdict),
+            Err(e) => log::warn!("{}", e),
+        }
+    }
 
-        Ok(RoomInfo {
-            room_id: room_id.to_string(),
-            client_count,
-            status: status.to_string(),
-            config,
-        })
+    // Clock sync is best effort; without it workers start on receipt
+    async fn refresh_clock(&self) {
+        if let Err(e) = self.sync_clock().await {
+            log::warn!("Clock sync failed: {}", e);
+        }
     }
 
     pub async fn configure_test(&self, config: &TestConfig) -> Result<(), String> {
-        let config_json = serde_json::to_value(config)
-            .map_err(|e| format!("Failed to serialize test config: {}", e))?;
+        // Catch a mistyped threshold before every worker has run the test
+        thresholds::validate(&config.thresholds)?;
+        let request = ConfigureRequest { config: config.clone() };
 
         // Send the message and wait for response
         self.send_message_and_wait_for_response(
             "configure-test",
-            serde_json::json!({ "config": config_json })
+            to_payload(&request)?
         ).await?;
 
         // Success check is already done in send_message_and_wait_for_response
         Ok(())
     }
 
-    pub async fn start_test(&self) -> Result<(), String> {
+    // The coordinator schedules the start `start_delay_ms` ahead so every
+    // worker has received the broadcast before anyone begins
+    pub async fn start_test(&self, start_delay_ms: u64) -> Result<(), String> {
         // Send the message and wait for response
         self.send_message_and_wait_for_response(
             "start-test",
-            serde_json::json!({})
+            to_payload(&StartRequest { start_delay_ms })?
         ).await?;
 
         // Success check is already done in send_message_and_wait_for_response
         Ok(())
     }
 
-    pub async fn submit_results(&self, results: &TestResult) -> Result<(), String> {
-        let results_json = serde_json::to_value(results)
-            .map_err(|e| format!("Failed to serialize test results: {}", e))?;
-
-        // Send the message and wait for response
+    // Stops the running test on every worker in the room
+    pub async fn abort_test(&self, reason: &str) -> Result<(), String> {
         self.send_message_and_wait_for_response(
-            "submit-results",
-            serde_json::json!({ "results": results_json })
+            "abort-test",
+            to_payload(&AbortRequest { reason: reason.to_string() })?
         ).await?;
 
         // Success check is already done in send_message_and_wait_for_response
         Ok(())
     }
 
+    // Results that can't be delivered because the connection is down are
+    // queued and resent after the next successful reconnect
+    pub async fn submit_results(&self, results: &TestResult) -> Result<(), String> {
+        let request = SubmitResultsRequest {
+            results: results.clone(),
+            declined: None,
+        };
+
+        // Send the message and wait for response
+        let sent = self.send_message_and_wait_for_response(
+            "submit-results",
+            to_payload(&request)?
+        ).await;
+
+        match sent {
+            Err(e) if !self.is_connected().await && !self.closing.load(Ordering::SeqCst) => {
+                log::warn!("Offline, queueing results {} for resubmission: {}", results.test_id, e);
+                self.pending_results.lock().await.push(results.clone());
+                Ok(())
+            }
+            // Success check is already done in send_message_and_wait_for_response
+            sent => sent.map(|_| ()),
+        }
+    }
+
+    // Tells the room this client won't submit results for the current run.
+    // Results submitted later, while the run is still open, are still counted.
+    pub async fn decline_test(&self, reason: &str) -> Result<(), String> {
+        let request = SubmitResultsRequest {
+            results: TestResult::default(),
+            declined: Some(reason.to_string()),
+        };
+        self.send_message_and_wait_for_response("submit-results", to_payload(&request)?)
+            .await
+            .map(|_| ())
+    }
+
     pub async fn leave_room(&self) -> Result<(), String> {
         // Send the message and wait for response
         self.send_message_and_wait_for_response(
@@ -292,7 +1005,56 @@ impl WebSocketClient {
             serde_json::json!({})
         ).await?;
 
+        *self.room_id.lock().await = None;
+        self.registered.store(false, Ordering::SeqCst);
+
         // Success check is already done in send_message_and_wait_for_response
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_missed_pong_threshold_is_rejected() {
+        let heartbeat = HeartbeatConfig {
+            interval_ms: 1000,
+            missed_pong_threshold: 0,
+        };
+        assert!(heartbeat.validate().is_err());
+        assert!(HeartbeatConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn unanswered_pings_count_up_to_the_threshold() {
+        let mut state = HeartbeatState::default();
+        assert_eq!(state.next_ping(3), Some(1));
+        assert_eq!(state.next_ping(3), Some(2));
+        assert_eq!(state.health.missed_pongs, 1);
+        assert_eq!(state.next_ping(3), Some(3));
+        assert_eq!(state.next_ping(3), None);
+        assert_eq!(state.health.missed_pongs, 3);
+    }
+
+    #[test]
+    fn a_pong_resets_the_count_and_measures_the_round_trip() {
+        let mut state = HeartbeatState::default();
+        let first = state.next_ping(3).unwrap();
+        let second = state.next_ping(3).unwrap();
+        assert_eq!(state.health.missed_pongs, 1);
+
+        std::thread::sleep(Duration::from_millis(20));
+        // The late answer to the first ping is ignored
+        assert!(!state.record_pong(&first.to_be_bytes()));
+        assert!(!state.record_pong(b"garbage"));
+        assert_eq!(state.health.missed_pongs, 1);
+
+        assert!(state.record_pong(&second.to_be_bytes()));
+        assert_eq!(state.health.missed_pongs, 0);
+        let rtt = state.health.rtt_ms.unwrap();
+        assert!((20.0..1000.0).contains(&rtt), "{}", rtt);
+        assert_eq!(state.next_ping(1), Some(3));
+    }
+}
@@ -0,0 +1,441 @@
+// Drives the Rust coordinator in-process with real WebSocketClients
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use armandra_lib::coordinator::Coordinator;
+use armandra_lib::protocol::{AggregatedResult, RoomEvent};
+use armandra_lib::stress_test::TestConfig;
+use armandra_lib::websocket::{HeartbeatConfig, WebSocketClient};
+use tokio::io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+const HEARTBEAT: HeartbeatConfig = HeartbeatConfig {
+    interval_ms: 200,
+    missed_pong_threshold: 3,
+};
+
+// A keep-alive HTTP server that answers every request with 200
+async fn target() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 4096];
+                while matches!(stream.read(&mut buffer).await, Ok(read) if read > 0) {
+                    let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                    if stream.write_all(response).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    format!("http://{}/", addr)
+}
+
+fn config(target: &str, extra: serde_json::Value) -> TestConfig {
+    let mut config = serde_json::json!({
+        "url": target,
+        "method": "GET",
+        "headers": {},
+        "requests_per_client": 10,
+        "concurrency": 2,
+        "timeout_ms": 2000,
+    });
+    config
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    serde_json::from_value(config).unwrap()
+}
+
+async fn completion(events: &mut broadcast::Receiver<RoomEvent>) -> AggregatedResult {
+    tokio::time::timeout(Duration::from_secs(20), async {
+        loop {
+            if let RoomEvent::TestCompleted(aggregated) = events.recv().await.unwrap() {
+                return aggregated;
+            }
+        }
+    })
+    .await
+    .expect("the room never completed")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn two_clients_run_and_aggregate() {
+    let target = target().await;
+    let coordinator = Coordinator::bind("127.0.0.1:0").await.unwrap();
+    let host = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let worker = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    worker.set_capacity(3);
+    let mut events = host.subscribe();
+
+    let room = host.create_room().await.unwrap();
+    let joined = worker.join_room(&room.room_id).await.unwrap();
+    assert_eq!(joined.room_id, room.room_id);
+    assert_eq!(joined.members.len(), 2);
+
+    let config = config(
+        &target,
+        serde_json::json!({
+            "load_target": { "type": "requests", "total": 400 },
+            "thresholds": ["p95 < 1s", "requests >= 400"],
+        }),
+    );
+    host.configure_test(&config).await.unwrap();
+    host.start_test(200).await.unwrap();
+
+    // Both clients run their share automatically and submit it
+    let aggregated = completion(&mut events).await;
+    assert_eq!(aggregated.client_count, 2);
+    assert_eq!(aggregated.result.total_requests, 400);
+    assert_eq!(aggregated.result.failed_requests, 0);
+    assert_eq!(aggregated.result.status_codes.get("200"), Some(&400));
+    assert_eq!(aggregated.result.latency_histogram.count(), 400);
+    assert!(aggregated.result.p99_response_time > 0.0);
+    assert!(aggregated.result.verdict.as_ref().unwrap().passed);
+    coordinator.shutdown();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn only_the_host_configures_and_starts() {
+    let target = target().await;
+    let coordinator = Coordinator::bind("127.0.0.1:0").await.unwrap();
+    let host = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let worker = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+
+    let room = host.create_room().await.unwrap();
+    worker.join_room(&room.room_id).await.unwrap();
+
+    let config = config(&target, serde_json::json!({}));
+    assert!(worker.configure_test(&config).await.is_err());
+    assert!(
+        host.start_test(0).await.is_err(),
+        "started before being configured"
+    );
+    host.configure_test(&config).await.unwrap();
+    assert!(worker.start_test(0).await.is_err());
+    assert!(worker.join_room("no-such-room").await.is_err());
+    coordinator.shutdown();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn abort_stops_every_worker() {
+    let target = target().await;
+    let coordinator = Coordinator::bind("127.0.0.1:0").await.unwrap();
+    let host = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let worker = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let mut events = host.subscribe();
+
+    let room = host.create_room().await.unwrap();
+    worker.join_room(&room.room_id).await.unwrap();
+    let config = config(
+        &target,
+        serde_json::json!({ "duration_ms": 30000, "rate_per_second": 20 }),
+    );
+    host.configure_test(&config).await.unwrap();
+    host.start_test(200).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    worker.abort_test("manual stop").await.unwrap();
+
+    let aggregated = completion(&mut events).await;
+    assert_eq!(aggregated.result.aborted.as_deref(), Some("manual stop"));
+    assert_eq!(aggregated.client_count, 2);
+    assert!(aggregated.result.duration < 10_000.0);
+    coordinator.shutdown();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn registered_agent_is_assigned_to_a_room() {
+    let target = target().await;
+    let coordinator = Coordinator::bind("127.0.0.1:0").await.unwrap();
+    let host = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let agent = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    agent.register_worker().await.unwrap();
+    let mut events = host.subscribe();
+    let mut agent_events = agent.subscribe();
+
+    let room = host.create_room().await.unwrap();
+    let assigned = host.assign_workers(None).await.unwrap();
+    assert_eq!(assigned, vec![agent.client_id().await.unwrap()]);
+
+    // Joining on assignment is up to the agent
+    let assigned_room = loop {
+        if let RoomEvent::RoomAssigned { room_id } = agent_events.recv().await.unwrap() {
+            break room_id;
+        }
+    };
+    assert_eq!(assigned_room, room.room_id);
+    agent.join_room(&assigned_room).await.unwrap();
+
+    let config = config(
+        &target,
+        serde_json::json!({ "load_target": { "type": "requests", "total": 300 } }),
+    );
+    host.configure_test(&config).await.unwrap();
+    host.start_test(200).await.unwrap();
+    let aggregated = completion(&mut events).await;
+    assert_eq!(aggregated.result.total_requests, 300);
+    coordinator.shutdown();
+}
+
+// Runs a load target across a host and a worker of capacity 3 and returns
+// the merged request count
+async fn run_uneven_target(load_target: serde_json::Value, requests_per_client: u32) -> u32 {
+    let target = target().await;
+    let coordinator = Coordinator::bind("127.0.0.1:0").await.unwrap();
+    let host = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let worker = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    worker.set_capacity(3);
+    let mut events = host.subscribe();
+
+    let room = host.create_room().await.unwrap();
+    worker.join_room(&room.room_id).await.unwrap();
+    let config = config(
+        &target,
+        serde_json::json!({
+            "load_target": load_target,
+            "requests_per_client": requests_per_client,
+            "concurrency": 4,
+        }),
+    );
+    host.configure_test(&config).await.unwrap();
+    host.start_test(200).await.unwrap();
+
+    let aggregated = completion(&mut events).await;
+    coordinator.shutdown();
+    aggregated.result.total_requests
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn request_targets_that_dont_divide_by_concurrency_run_in_full() {
+    // Shares of 100 and 301, the latter split 76/75/75/75 across tasks
+    let total =
+        run_uneven_target(serde_json::json!({ "type": "requests", "total": 401 }), 10).await;
+    assert_eq!(total, 401);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rate_targets_that_dont_divide_by_concurrency_run_in_full() {
+    // 2 x 13 requests, split between the two clients by capacity
+    let total = run_uneven_target(serde_json::json!({ "type": "rps", "total": 400.0 }), 13).await;
+    assert_eq!(total, 26);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_declining_worker_is_left_out() {
+    let target = target().await;
+    let coordinator = Coordinator::bind("127.0.0.1:0").await.unwrap();
+    let host = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let worker = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    worker.set_auto_run(false);
+    let mut events = host.subscribe();
+
+    let room = host.create_room().await.unwrap();
+    worker.join_room(&room.room_id).await.unwrap();
+    let config = config(&target, serde_json::json!({}));
+    host.configure_test(&config).await.unwrap();
+    host.start_test(200).await.unwrap();
+
+    let aggregated = completion(&mut events).await;
+    assert_eq!(aggregated.client_count, 1);
+    assert_eq!(aggregated.result.total_requests, 10);
+    let worker_id = worker.client_id().await.unwrap();
+    assert_eq!(
+        aggregated.declined.get(&worker_id).map(String::as_str),
+        Some("Auto-run is off")
+    );
+    coordinator.shutdown();
+}
+
+// Forwards connections to `upstream` until `cut` drops the open ones
+struct Proxy {
+    url: String,
+    connections: Arc<std::sync::Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Proxy {
+    async fn start(upstream: SocketAddr) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let accepted = Arc::clone(&connections);
+        tokio::spawn(async move {
+            while let Ok((mut inbound, _)) = listener.accept().await {
+                let connection = tokio::spawn(async move {
+                    let mut outbound = TcpStream::connect(upstream).await.unwrap();
+                    let _ = copy_bidirectional(&mut inbound, &mut outbound).await;
+                });
+                accepted.lock().unwrap().push(connection);
+            }
+        });
+        Proxy { url, connections }
+    }
+
+    fn cut(&self) {
+        for connection in self.connections.lock().unwrap().drain(..) {
+            connection.abort();
+        }
+    }
+}
+
+async fn next_event(
+    events: &mut broadcast::Receiver<RoomEvent>,
+    wanted: impl Fn(&RoomEvent) -> bool,
+) -> RoomEvent {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let event = events.recv().await.unwrap();
+            if wanted(&event) {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("the event never arrived")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_reconnecting_host_resumes_its_session() {
+    let target = target().await;
+    let coordinator = Coordinator::bind_with_grace("127.0.0.1:0", Duration::from_secs(10))
+        .await
+        .unwrap();
+    let proxy = Proxy::start(coordinator.local_addr()).await;
+    let host = WebSocketClient::new(&proxy.url, HEARTBEAT).await.unwrap();
+    let worker = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let mut host_events = host.subscribe();
+    let mut worker_events = worker.subscribe();
+
+    let room = host.create_room().await.unwrap();
+    worker.join_room(&room.room_id).await.unwrap();
+    let host_id = host.client_id().await.unwrap();
+
+    proxy.cut();
+    next_event(&mut host_events, |event| {
+        matches!(event, RoomEvent::Disconnected)
+    })
+    .await;
+    next_event(
+        &mut host_events,
+        |event| matches!(event, RoomEvent::Connected { client_id, .. } if *client_id == host_id),
+    )
+    .await;
+    assert_eq!(host.client_id().await.as_ref(), Some(&host_id));
+
+    // Still the host, and nobody saw it leave
+    let config = config(&target, serde_json::json!({}));
+    host.configure_test(&config).await.unwrap();
+    host.start_test(200).await.unwrap();
+    let aggregated = completion(&mut host_events).await;
+    assert_eq!(aggregated.client_count, 2);
+    assert_eq!(aggregated.result.total_requests, 20);
+    while let Ok(event) = worker_events.try_recv() {
+        assert!(!matches!(
+            event,
+            RoomEvent::ClientLeft { .. } | RoomEvent::HostChanged { .. }
+        ));
+    }
+    coordinator.shutdown();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn results_for_a_completed_run_are_rejected() {
+    let target = target().await;
+    let coordinator = Coordinator::bind("127.0.0.1:0").await.unwrap();
+    let host = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let mut events = host.subscribe();
+
+    host.create_room().await.unwrap();
+    host.configure_test(&config(&target, serde_json::json!({})))
+        .await
+        .unwrap();
+    host.start_test(0).await.unwrap();
+    let aggregated = completion(&mut events).await;
+
+    let error = host.submit_results(&aggregated.result).await.unwrap_err();
+    assert!(error.contains("already completed"), "{}", error);
+    coordinator.shutdown();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn an_abort_before_the_start_time_ends_the_wait() {
+    let target = target().await;
+    let coordinator = Coordinator::bind("127.0.0.1:0").await.unwrap();
+    let host = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let worker = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let mut events = host.subscribe();
+
+    let room = host.create_room().await.unwrap();
+    worker.join_room(&room.room_id).await.unwrap();
+    host.configure_test(&config(&target, serde_json::json!({})))
+        .await
+        .unwrap();
+    host.start_test(60_000).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    host.abort_test("called off").await.unwrap();
+
+    let aggregated = completion(&mut events).await;
+    assert_eq!(aggregated.result.aborted.as_deref(), Some("called off"));
+    assert_eq!(aggregated.result.total_requests, 0);
+    coordinator.shutdown();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn far_future_start_times_are_declined() {
+    let target = target().await;
+    let coordinator = Coordinator::bind("127.0.0.1:0").await.unwrap();
+    let host = WebSocketClient::new(&coordinator.url(), HEARTBEAT)
+        .await
+        .unwrap();
+    let mut events = host.subscribe();
+
+    host.create_room().await.unwrap();
+    host.configure_test(&config(&target, serde_json::json!({})))
+        .await
+        .unwrap();
+    host.start_test(3_600_000).await.unwrap();
+
+    let aggregated = completion(&mut events).await;
+    assert_eq!(aggregated.client_count, 0);
+    let host_id = host.client_id().await.unwrap();
+    assert!(aggregated.declined[&host_id].contains("allowed"));
+    coordinator.shutdown();
+}
@@ -1,5 +1,5 @@
-import { BarChart, Bar, XAxis, YAxis, CartesianGrid, Tooltip, Legend, ResponsiveContainer, PieChart, Pie, Cell } from 'recharts';
-import useStore, { AggregatedResult } from '../store/useStore';
+import { BarChart, Bar, LineChart, Line, XAxis, YAxis, CartesianGrid, Tooltip, Legend, ResponsiveContainer, PieChart, Pie, Cell } from 'recharts';
+import useStore, { AggregatedResult, TestResult } from '../store/useStore';
 
 const COLORS = ['#facc15', '#10b981', '#0ea5e9', '#f43f5e', '#8b5cf6'];
 
@@ -32,6 +32,34 @@ const ResultsDisplay: React.FC = () => {
     { name: 'Failed', value: result.failed_requests },
   ];
 
+  // Two parts of the run side by side: warm-up and steady state, or the
+  // bursts of a spike run and the recovery after them
+  const comparisonRows: [string, (r: TestResult) => string][] = [
+    ['Requests', (r) => r.total_requests.toLocaleString()],
+    ['Failed', (r) => r.failed_requests.toLocaleString()],
+    ['Avg', (r) => `${r.avg_response_time.toFixed(2)} ms`],
+    ['p95', (r) => `${(r.p95_response_time ?? 0).toFixed(2)} ms`],
+    ['p99', (r) => `${(r.p99_response_time ?? 0).toFixed(2)} ms`],
+    ['Throughput', (r) => `${r.throughput.toFixed(2)} req/s`],
+  ];
+
+  const correctedRows: [string, number | undefined, number][] = result.corrected
+    ? [
+        ['p50', result.p50_response_time, result.corrected.p50_response_time],
+        ['p90', result.p90_response_time, result.corrected.p90_response_time],
+        ['p95', result.p95_response_time, result.corrected.p95_response_time],
+        ['p99', result.p99_response_time, result.corrected.p99_response_time],
+        ['Max', result.max_response_time, result.corrected.max_response_time],
+      ]
+    : [];
+
+  // One point per soak interval, labelled by minutes into the run
+  const soakData = (result.soak?.intervals || []).map((interval) => ({
+    name: (interval.end / 60000).toFixed(1),
+    p99: interval.p99_response_time,
+    throughput: interval.throughput,
+  }));
+
   // Calculate success rate
   const successRate = result.total_requests > 0
     ? ((result.successful_requests / result.total_requests) * 100)
@@ -49,6 +77,42 @@ const ResultsDisplay: React.FC = () => {
         <span className="ml-auto badge badge-success">Completed</span>
       </div>
 
+      {result.aborted && (
+        <div className="bg-red-50 dark:bg-red-900/30 border border-red-200 dark:border-red-800 rounded-xl p-4 mb-6 text-red-600 dark:text-red-400">
+          <p className="font-bold">Aborted early</p>
+          <p className="text-sm">{result.aborted}</p>
+        </div>
+      )}
+
+      {result.verdict && (
+        <div
+          className={`rounded-xl p-4 mb-6 border ${
+            result.verdict.passed
+              ? 'bg-green-50 dark:bg-green-900/30 border-green-200 dark:border-green-800'
+              : 'bg-red-50 dark:bg-red-900/30 border-red-200 dark:border-red-800'
+          }`}
+        >
+          <p className={`text-lg font-bold mb-2 ${result.verdict.passed ? 'text-green-600 dark:text-green-400' : 'text-red-600 dark:text-red-400'}`}>
+            {result.verdict.passed ? 'PASSED' : 'FAILED'}
+          </p>
+          <table className="w-full text-sm">
+            <tbody>
+              {result.verdict.thresholds.map((threshold) => (
+                <tr key={threshold.expression}>
+                  <td className="py-1 font-mono">{threshold.expression}</td>
+                  <td className="py-1 text-right">
+                    {threshold.observed === null ? 'no data' : `${threshold.observed.toFixed(2)} ${threshold.unit}`}
+                  </td>
+                  <td className={`py-1 pl-4 text-right font-medium ${threshold.passed ? 'text-green-600 dark:text-green-400' : 'text-red-600 dark:text-red-400'}`}>
+                    {threshold.passed ? 'Pass' : 'Fail'}
+                  </td>
+                </tr>
+              ))}
+            </tbody>
+          </table>
+        </div>
+      )}
+
       <div className="grid grid-cols-1 lg:grid-cols-3 gap-4 mb-8">
         <div className="lg:col-span-2 bg-white dark:bg-gray-700 p-5 rounded-xl shadow-soft border border-gray-100 dark:border-gray-600">
           <div className="flex items-center mb-3">
@@ -111,7 +175,7 @@ const ResultsDisplay: React.FC = () => {
         </div>
       </div>
 
-      {'clientCount' in result && (
+      {'client_count' in result && (
         <div className="bg-white dark:bg-gray-700 p-4 rounded-xl shadow-soft border border-gray-100 dark:border-gray-600 mb-8">
           <div className="flex items-center">
             <svg xmlns="http://www.w3.org/2000/svg" className="h-5 w-5 mr-2 text-armandra" viewBox="0 0 20 20" fill="currentColor">
@@ -119,12 +183,122 @@ const ResultsDisplay: React.FC = () => {
             </svg>
             <h3 className="text-lg font-bold">Distributed Test</h3>
             <div className="ml-auto bg-blue-100 text-blue-800 dark:bg-blue-900 dark:text-blue-100 px-3 py-1 rounded-full text-sm font-medium">
-              {(result as AggregatedResult).clientCount} Clients
+              {(result as AggregatedResult).client_count} Clients
             </div>
           </div>
         </div>
       )}
 
+      {result.warmup && (
+        <div className="bg-white dark:bg-gray-700 p-5 rounded-xl shadow-soft border border-gray-100 dark:border-gray-600 mb-8">
+          <h3 className="text-lg font-bold mb-3">Warm-up vs Steady State</h3>
+          <table className="w-full text-sm">
+            <thead>
+              <tr className="text-gray-500 dark:text-gray-400">
+                <th className="py-1 text-left font-normal"></th>
+                <th className="py-1 text-right font-normal">Warm-up</th>
+                <th className="py-1 text-right font-normal">Measured</th>
+              </tr>
+            </thead>
+            <tbody>
+              {comparisonRows.map(([label, format]) => (
+                <tr key={label}>
+                  <td className="py-1">{label}</td>
+                  <td className="py-1 text-right">{format(result.warmup!)}</td>
+                  <td className="py-1 text-right font-medium">{format(result)}</td>
+                </tr>
+              ))}
+            </tbody>
+          </table>
+        </div>
+      )}
+
+      {result.spike && (
+        <div className="bg-white dark:bg-gray-700 p-5 rounded-xl shadow-soft border border-gray-100 dark:border-gray-600 mb-8">
+          <h3 className="text-lg font-bold mb-1">Bursts vs Recovery</h3>
+          <p className="text-xs text-gray-500 dark:text-gray-400 mb-3">
+            {result.spike.bursts} burst{result.spike.bursts === 1 ? '' : 's'}; these requests also count in the totals above.
+          </p>
+          <table className="w-full text-sm">
+            <thead>
+              <tr className="text-gray-500 dark:text-gray-400">
+                <th className="py-1 text-left font-normal"></th>
+                <th className="py-1 text-right font-normal">Burst</th>
+                <th className="py-1 text-right font-normal">Recovery</th>
+              </tr>
+            </thead>
+            <tbody>
+              {comparisonRows.map(([label, format]) => (
+                <tr key={label}>
+                  <td className="py-1">{label}</td>
+                  <td className="py-1 text-right font-medium">{format(result.spike!.burst)}</td>
+                  <td className="py-1 text-right">{format(result.spike!.recovery)}</td>
+                </tr>
+              ))}
+            </tbody>
+          </table>
+        </div>
+      )}
+
+      {result.corrected && (
+        <div className="bg-white dark:bg-gray-700 p-5 rounded-xl shadow-soft border border-gray-100 dark:border-gray-600 mb-8">
+          <h3 className="text-lg font-bold mb-1">Coordinated Omission</h3>
+          <p className="text-xs text-gray-500 dark:text-gray-400 mb-3">
+            Corrected figures count the time requests spent waiting behind a slow response.
+          </p>
+          <table className="w-full text-sm">
+            <thead>
+              <tr className="text-gray-500 dark:text-gray-400">
+                <th className="py-1 text-left font-normal"></th>
+                <th className="py-1 text-right font-normal">Measured</th>
+                <th className="py-1 text-right font-normal">Corrected</th>
+              </tr>
+            </thead>
+            <tbody>
+              {correctedRows.map(([label, measured, corrected]) => (
+                <tr key={label}>
+                  <td className="py-1">{label}</td>
+                  <td className="py-1 text-right">{(measured ?? 0).toFixed(2)} ms</td>
+                  <td className="py-1 text-right font-medium">{corrected.toFixed(2)} ms</td>
+                </tr>
+              ))}
+            </tbody>
+          </table>
+        </div>
+      )}
+
+      {result.soak && (
+        <div className="bg-white dark:bg-gray-700 p-5 rounded-xl shadow-soft border border-gray-100 dark:border-gray-600 mb-8">
+          <h3 className="text-lg font-bold mb-3">Soak Trends</h3>
+          {result.soak.trends.length === 0 ? (
+            <p className="text-sm text-green-600 dark:text-green-400 mb-4">No drift detected</p>
+          ) : (
+            <ul className="text-sm text-red-600 dark:text-red-400 mb-4 list-disc pl-5">
+              {result.soak.trends.map((trend, index) => (
+                <li key={index}>{trend.description}</li>
+              ))}
+            </ul>
+          )}
+          <div className="h-64">
+            <ResponsiveContainer width="100%" height="100%">
+              <LineChart data={soakData}>
+                <CartesianGrid strokeDasharray="3 3" />
+                <XAxis dataKey="name" unit=" min" />
+                <YAxis yAxisId="latency" unit=" ms" />
+                <YAxis yAxisId="throughput" orientation="right" unit=" req/s" />
+                <Tooltip formatter={(value: number) => value.toFixed(2)} />
+                <Legend />
+                <Line yAxisId="latency" type="monotone" dataKey="p99" stroke="#f43f5e" name="p99 (ms)" dot={false} />
+                <Line yAxisId="throughput" type="monotone" dataKey="throughput" stroke="#0ea5e9" name="Throughput (req/s)" dot={false} />
+              </LineChart>
+            </ResponsiveContainer>
+          </div>
+          {result.soak.snapshot_file && (
+            <p className="text-xs text-gray-500 dark:text-gray-400 mt-2 font-mono">{result.soak.snapshot_file}</p>
+          )}
+        </div>
+      )}
+
       <div className="space-y-8">
         <div className="bg-white dark:bg-gray-700 p-5 rounded-xl shadow-soft border border-gray-100 dark:border-gray-600">
           <h3 className="text-lg font-bold mb-4 flex items-center">
@@ -2,7 +2,7 @@ import React, { useState } from 'react';
 import useStore from '../store/useStore';
 
 const RoomManager: React.FC = () => {
-  const { isConnected, room, createRoom, joinRoom, leaveRoom } = useStore();
+  const { isConnected, room, createRoom, joinRoom, leaveRoom, assignWorkers } = useStore();
   const [roomId, setRoomId] = useState('');
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
@@ -49,6 +49,19 @@ const RoomManager: React.FC = () => {
     }
   };
 
+  const handleAssignWorkers = async () => {
+    setLoading(true);
+    setError(null);
+
+    const assigned = await assignWorkers();
+    if (assigned === null) {
+      setError('Failed to assign workers.');
+    } else if (assigned.length === 0) {
+      setError('No idle workers are waiting for a room.');
+    }
+    setLoading(false);
+  };
+
   const handleLeaveRoom = async () => {
     setLoading(true);
     await leaveRoom();
@@ -118,8 +131,46 @@ const RoomManager: React.FC = () => {
               </p>
             </div>
           </div>
+
+          {room.members.length > 0 && (
+            <div className="mt-4 space-y-2">
+              <p className="text-xs text-gray-500 dark:text-gray-400">Workers</p>
+              {room.members.map((member) => (
+                <div
+                  key={member.client_id}
+                  className="text-sm bg-gray-50 dark:bg-gray-800 p-2 rounded border border-gray-200 dark:border-gray-700"
+                >
+                  <p className="font-medium">
+                    {member.worker?.label || member.worker?.hostname || member.client_id}
+                  </p>
+                  {member.worker && (
+                    <p className="text-xs text-gray-500 dark:text-gray-400">
+                      {member.worker.os} · {member.worker.cpu_cores} cores ·{' '}
+                      {(member.worker.available_memory / 1024 ** 3).toFixed(1)} GB free
+                      {member.worker.max_concurrency != null && ` · up to ${member.worker.max_concurrency} VUs`}
+                      {` · capacity ${member.capacity}`}
+                    </p>
+                  )}
+                </div>
+              ))}
+            </div>
+          )}
         </div>
 
+        {error && (
+          <p className="text-sm text-red-600 dark:text-red-400 mb-4">{error}</p>
+        )}
+
+        {room.isHost && (
+          <button
+            onClick={handleAssignWorkers}
+            className="btn btn-secondary w-full mb-3"
+            disabled={loading}
+          >
+            Assign Idle Workers
+          </button>
+        )}
+
         <button
           onClick={handleLeaveRoom}
           className="btn btn-danger w-full"
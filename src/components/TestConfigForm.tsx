@@ -1,5 +1,5 @@
 import React, { useState } from 'react';
-import useStore, { TestConfig } from '../store/useStore';
+import useStore, { AbortConditions, TestConfig, ThinkTime } from '../store/useStore';
 
 const TestConfigForm: React.FC = () => {
   const { room, testConfig, configureTest } = useStore();
@@ -16,6 +16,9 @@ const TestConfigForm: React.FC = () => {
   const [headerValue, setHeaderValue] = useState('');
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [copied, setCopied] = useState(false);
+  // Kept as typed so blank lines survive until the next keystroke
+  const [thresholdText, setThresholdText] = useState((config.thresholds || []).join('\n'));
 
   const handleChange = (e: React.ChangeEvent<HTMLInputElement | HTMLSelectElement | HTMLTextAreaElement>) => {
     const { name, value } = e.target;
@@ -33,6 +36,42 @@ const TestConfigForm: React.FC = () => {
     }
   };
 
+  // Dropped entirely once neither limit is set
+  const setAbortCondition = (change: Partial<AbortConditions>) => {
+    const next = { window_ms: 10000, min_requests: 10, ...config.abort_conditions, ...change };
+    setConfig({
+      ...config,
+      abort_conditions: next.max_error_rate == null && next.max_p99_ms == null ? null : next,
+    });
+  };
+
+  // Each distribution takes one or two numbers; `a` and `b` fill them in order
+  const setThinkTime = (type: string, a: number, b: number) => {
+    const thinkTimes: Record<string, ThinkTime> = {
+      fixed: { type: 'fixed', ms: a },
+      uniform: { type: 'uniform', min_ms: a, max_ms: b },
+      normal: { type: 'normal', mean_ms: a, std_dev_ms: b },
+      exponential: { type: 'exponential', mean_ms: a },
+    };
+    setConfig({ ...config, think_time: thinkTimes[type] ?? null });
+  };
+
+  const thinkValues = ((): [number, number] => {
+    const thinkTime = config.think_time;
+    switch (thinkTime?.type) {
+      case 'fixed':
+        return [thinkTime.ms, 0];
+      case 'uniform':
+        return [thinkTime.min_ms, thinkTime.max_ms];
+      case 'normal':
+        return [thinkTime.mean_ms, thinkTime.std_dev_ms];
+      case 'exponential':
+        return [thinkTime.mean_ms, 0];
+      default:
+        return [0, 0];
+    }
+  })();
+
   const handleAddHeader = () => {
     if (!headerKey.trim()) return;
 
@@ -58,6 +97,17 @@ const TestConfigForm: React.FC = () => {
     });
   };
 
+  // The same JSON runs headless with `armandra-cli config.json`
+  const handleCopyConfig = async () => {
+    try {
+      await navigator.clipboard.writeText(JSON.stringify(config, null, 2));
+      setCopied(true);
+      setTimeout(() => setCopied(false), 2000);
+    } catch (err) {
+      setError(`Failed to copy config: ${err}`);
+    }
+  };
+
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
 
@@ -230,6 +280,162 @@ const TestConfigForm: React.FC = () => {
           </div>
         </div>
 
+        <div className="grid grid-cols-4 gap-4">
+          <div>
+            <label htmlFor="think_time" className="block text-sm font-medium mb-2">
+              Think Time
+            </label>
+            <select
+              id="think_time"
+              value={config.think_time?.type ?? 'none'}
+              onChange={(e) => setThinkTime(e.target.value, thinkValues[0], thinkValues[1])}
+              className="input w-full"
+              disabled={loading || !isConfigurable}
+            >
+              <option value="none">None</option>
+              <option value="fixed">Fixed</option>
+              <option value="uniform">Uniform</option>
+              <option value="normal">Normal</option>
+              <option value="exponential">Exponential</option>
+            </select>
+          </div>
+          <div>
+            <label htmlFor="think_a" className="block text-sm font-medium mb-2">
+              {config.think_time?.type === 'uniform' ? 'Min (ms)' : config.think_time?.type === 'fixed' ? 'Pause (ms)' : 'Mean (ms)'}
+            </label>
+            <input
+              id="think_a"
+              type="number"
+              value={thinkValues[0]}
+              onChange={(e) => setThinkTime(config.think_time!.type, Number(e.target.value), thinkValues[1])}
+              className="input w-full"
+              min="0"
+              disabled={loading || !isConfigurable || !config.think_time}
+            />
+          </div>
+          <div>
+            <label htmlFor="think_b" className="block text-sm font-medium mb-2">
+              {config.think_time?.type === 'uniform' ? 'Max (ms)' : 'Std Dev (ms)'}
+            </label>
+            <input
+              id="think_b"
+              type="number"
+              value={thinkValues[1]}
+              onChange={(e) => setThinkTime(config.think_time!.type, thinkValues[0], Number(e.target.value))}
+              className="input w-full"
+              min="0"
+              disabled={
+                loading || !isConfigurable || (config.think_time?.type !== 'uniform' && config.think_time?.type !== 'normal')
+              }
+            />
+          </div>
+          <div>
+            <label htmlFor="pacing_ms" className="block text-sm font-medium mb-2">
+              Pacing (ms)
+            </label>
+            <input
+              id="pacing_ms"
+              type="number"
+              value={config.pacing_ms ?? ''}
+              onChange={(e) => setConfig({ ...config, pacing_ms: Number(e.target.value) > 0 ? Number(e.target.value) : null })}
+              className="input w-full"
+              min="0"
+              placeholder="None"
+              disabled={loading || !isConfigurable}
+            />
+          </div>
+        </div>
+
+        <div>
+          <label htmlFor="warmup" className="block text-sm font-medium mb-2">
+            Warm-up (s)
+          </label>
+          <input
+            id="warmup"
+            type="number"
+            value={config.warmup?.type === 'duration' ? config.warmup.ms / 1000 : ''}
+            onChange={(e) => {
+              const seconds = Number(e.target.value);
+              setConfig({
+                ...config,
+                warmup: seconds > 0 ? { type: 'duration', ms: seconds * 1000 } : null,
+              });
+            }}
+            className="input w-full"
+            min="0"
+            placeholder="None"
+            disabled={loading || !isConfigurable}
+          />
+        </div>
+
+        <div>
+          <label htmlFor="thresholds" className="block text-sm font-medium mb-2">
+            Pass/Fail Thresholds
+          </label>
+          <textarea
+            id="thresholds"
+            value={thresholdText}
+            onChange={(e) => {
+              setThresholdText(e.target.value);
+              setConfig({
+                ...config,
+                thresholds: e.target.value.split('\n').map((line) => line.trim()).filter(Boolean),
+              });
+            }}
+            className="input w-full h-20 font-mono text-sm"
+            placeholder={'One per line, e.g.\np95 < 300ms\nerror_rate < 1%\nthroughput > 500'}
+            disabled={loading || !isConfigurable}
+          />
+        </div>
+
+        <div className="grid grid-cols-3 gap-4">
+          <div>
+            <label htmlFor="abort_error_rate" className="block text-sm font-medium mb-2">
+              Abort Above Error Rate (%)
+            </label>
+            <input
+              id="abort_error_rate"
+              type="number"
+              value={config.abort_conditions?.max_error_rate ?? ''}
+              onChange={(e) => setAbortCondition({ max_error_rate: e.target.value === '' ? null : Number(e.target.value) })}
+              className="input w-full"
+              min="0"
+              max="100"
+              placeholder="Off"
+              disabled={loading || !isConfigurable}
+            />
+          </div>
+          <div>
+            <label htmlFor="abort_p99" className="block text-sm font-medium mb-2">
+              Abort Above p99 (ms)
+            </label>
+            <input
+              id="abort_p99"
+              type="number"
+              value={config.abort_conditions?.max_p99_ms ?? ''}
+              onChange={(e) => setAbortCondition({ max_p99_ms: e.target.value === '' ? null : Number(e.target.value) })}
+              className="input w-full"
+              min="1"
+              placeholder="Off"
+              disabled={loading || !isConfigurable}
+            />
+          </div>
+          <div>
+            <label htmlFor="abort_window" className="block text-sm font-medium mb-2">
+              Sustained For (s)
+            </label>
+            <input
+              id="abort_window"
+              type="number"
+              value={(config.abort_conditions?.window_ms ?? 10000) / 1000}
+              onChange={(e) => setAbortCondition({ window_ms: Math.max(1, Number(e.target.value)) * 1000 })}
+              className="input w-full"
+              min="1"
+              disabled={loading || !isConfigurable || !config.abort_conditions}
+            />
+          </div>
+        </div>
+
         {(config.method === 'POST' || config.method === 'PUT' || config.method === 'PATCH') && (
           <div>
             <label htmlFor="body" className="block text-sm font-medium mb-2">
@@ -362,6 +568,15 @@ const TestConfigForm: React.FC = () => {
             )}
           </button>
         )}
+
+        <button
+          type="button"
+          onClick={handleCopyConfig}
+          className="btn btn-secondary w-full"
+          disabled={!config.url.trim()}
+        >
+          {copied ? 'Copied!' : 'Copy Config as JSON'}
+        </button>
       </form>
     </div>
   );
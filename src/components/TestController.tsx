@@ -2,7 +2,7 @@ import React, { useState } from 'react';
 import useStore from '../store/useStore';
 
 const TestController: React.FC = () => {
-  const { room, testConfig, startTest, runTest, submitResults } = useStore();
+  const { room, testConfig, startTest, runTest, submitResults, autoRun, setAutoRun, workerError, liveMetrics } = useStore();
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
 
@@ -107,6 +107,16 @@ const TestController: React.FC = () => {
           </div>
         )}
 
+        <label className="flex items-center text-sm text-gray-600 dark:text-gray-300 mb-4">
+          <input
+            type="checkbox"
+            className="mr-2"
+            checked={autoRun}
+            onChange={(e) => setAutoRun(e.target.checked)}
+          />
+          Run automatically and submit results
+        </label>
+
         {room.isHost ? (
           <button
             onClick={handleStartTest}
@@ -183,17 +193,49 @@ const TestController: React.FC = () => {
           </div>
         </div>
 
-        {error && (
+        {liveMetrics && (
+          <div className="grid grid-cols-4 gap-2 mb-4 text-center">
+            <div className="bg-white dark:bg-gray-700 rounded-lg p-2 border border-gray-100 dark:border-gray-600">
+              <p className="text-xs text-gray-500 dark:text-gray-400">Requests</p>
+              <p className="font-medium">{liveMetrics.total_requests}</p>
+            </div>
+            <div className="bg-white dark:bg-gray-700 rounded-lg p-2 border border-gray-100 dark:border-gray-600">
+              <p className="text-xs text-gray-500 dark:text-gray-400">Req/s</p>
+              <p className="font-medium">{liveMetrics.throughput.toFixed(1)}</p>
+            </div>
+            <div className="bg-white dark:bg-gray-700 rounded-lg p-2 border border-gray-100 dark:border-gray-600">
+              <p className="text-xs text-gray-500 dark:text-gray-400">p95</p>
+              <p className="font-medium">{liveMetrics.p95_response_time.toFixed(1)} ms</p>
+            </div>
+            <div className="bg-white dark:bg-gray-700 rounded-lg p-2 border border-gray-100 dark:border-gray-600">
+              <p className="text-xs text-gray-500 dark:text-gray-400">Workers</p>
+              <p className="font-medium">{liveMetrics.worker_count}</p>
+            </div>
+          </div>
+        )}
+
+        {(error || workerError) && (
           <div className="bg-red-50 dark:bg-red-900/30 border border-red-200 dark:border-red-800 rounded-lg p-3 text-red-600 dark:text-red-400 text-sm mb-4">
             <div className="flex">
               <svg xmlns="http://www.w3.org/2000/svg" className="h-5 w-5 mr-2 text-red-500" viewBox="0 0 20 20" fill="currentColor">
                 <path fillRule="evenodd" d="M18 10a8 8 0 11-16 0 8 8 0 0116 0zm-7 4a1 1 0 11-2 0 1 1 0 012 0zm-1-9a1 1 0 00-1 1v4a1 1 0 102 0V6a1 1 0 00-1-1z" clipRule="evenodd" />
               </svg>
-              {error}
+              {error || workerError}
             </div>
           </div>
         )}
 
+        <label className="flex items-center text-sm text-gray-600 dark:text-gray-300 mb-4">
+          <input
+            type="checkbox"
+            className="mr-2"
+            checked={autoRun}
+            onChange={(e) => setAutoRun(e.target.checked)}
+          />
+          Run automatically and submit results
+        </label>
+
+        {!autoRun && (
         <button
           onClick={handleRunTest}
           className="btn btn-primary w-full"
@@ -216,6 +258,7 @@ const TestController: React.FC = () => {
             </span>
           )}
         </button>
+        )}
       </div>
     );
   }
@@ -1,5 +1,6 @@
 import { create } from "zustand";
 import { invoke } from "@tauri-apps/api/core";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
 
 export interface TestConfig {
   url: string;
@@ -9,8 +10,89 @@ export interface TestConfig {
   requests_per_client: number;
   concurrency: number;
   timeout_ms: number;
+  resolve?: Record<string, string[]>;
+  redirect_policy?: RedirectPolicy;
+  body_source?: RequestBody;
+  raw_request?: string;
+  accept_encoding?: string[];
+  compress_body?: boolean;
+  duration_ms?: number | null;
+  rate_per_second?: number | null;
+  load_target?: LoadTarget | null;
+  thresholds?: string[];
+  abort_conditions?: AbortConditions | null;
+  warmup?: Warmup | null;
+  think_time?: ThinkTime | null;
+  pacing_ms?: number | null;
+  expected_interval_ms?: number | null;
+  soak?: SoakConfig | null;
+  spike?: SpikeProfile | null;
 }
 
+// Bursts of multiplier x rate_per_second at set times (ms from the start) or
+// every every_ms, each followed by a recovery window
+export interface SpikeProfile {
+  multiplier: number;
+  burst_ms: number;
+  at_ms?: number[];
+  every_ms?: number | null;
+  recovery_ms?: number | null;
+}
+
+// Long runs: per-interval summaries, snapshot files and drift detection
+export interface SoakConfig {
+  interval_ms: number;
+  snapshot_dir?: string | null;
+  latency_growth_pct?: number;
+  throughput_drop_pct?: number;
+  error_rate_rise?: number;
+}
+
+// Pause after each response, in milliseconds, drawn afresh every time
+export type ThinkTime =
+  | { type: "fixed"; ms: number }
+  | { type: "uniform"; min_ms: number; max_ms: number }
+  | { type: "normal"; mean_ms: number; std_dev_ms: number }
+  | { type: "exponential"; mean_ms: number };
+
+// Load sent before measuring starts, reported separately
+export type Warmup =
+  | { type: "duration"; ms: number }
+  | { type: "requests"; count: number };
+
+// Stops the test early when the trailing window breaches a limit
+export interface AbortConditions {
+  window_ms: number;
+  max_error_rate?: number | null;
+  max_p99_ms?: number | null;
+  min_requests: number;
+}
+
+// Room-wide load, split between workers by capacity when the test starts
+export type LoadTarget =
+  | { type: "requests"; total: number }
+  | { type: "rps"; total: number }
+  | { type: "virtual_users"; total: number };
+
+export interface MultipartPart {
+  name: string;
+  value?: string;
+  file?: string;
+  file_name?: string;
+  content_type?: string;
+}
+
+export type RequestBody =
+  | { type: "file"; path: string; content_type?: string }
+  | { type: "base64"; data: string; content_type?: string }
+  | { type: "multipart"; parts: MultipartPart[] }
+  | { type: "form"; fields: [string, string][] };
+
+export type RedirectPolicy =
+  | { mode: "none" }
+  | { mode: "limited"; max_redirects: number }
+  | { mode: "same_host"; max_redirects: number };
+
 export interface TestResult {
   total_requests: number;
   successful_requests: number;
@@ -24,17 +106,181 @@ export interface TestResult {
   throughput: number;
   test_id: string;
   timestamp: number;
+  resolved_addresses?: Record<string, AddressStats>;
+  redirect_counts?: Record<string, number>;
+  redirect_hops?: Record<string, number>[];
+  redirects_stopped?: Record<string, number>;
+  content_encodings?: Record<string, number>;
+  compressed_bytes?: number;
+  decompressed_bytes?: number;
+  decompression_time?: number;
+  decompression_errors?: number;
+  request_body_bytes?: number;
+  request_body_compressed_bytes?: number;
+  clock_offset?: number | null;
+  clock_uncertainty?: number | null;
+  p50_response_time?: number;
+  p90_response_time?: number;
+  p95_response_time?: number;
+  p99_response_time?: number;
+  verdict?: Verdict | null;
+  aborted?: string | null;
+  warmup?: TestResult | null;
+  corrected?: CorrectedLatency | null;
+  soak?: SoakReport | null;
+  spike?: SpikeReport | null;
+}
+
+// Burst and recovery requests, also counted in the overall figures
+export interface SpikeReport {
+  bursts: number;
+  burst: TestResult;
+  recovery: TestResult;
+}
+
+export interface SoakInterval {
+  start: number;
+  end: number;
+  requests: number;
+  failed_requests: number;
+  avg_response_time: number;
+  p50_response_time: number;
+  p99_response_time: number;
+  throughput: number;
+  error_rate: number;
+}
+
+export interface SoakTrend {
+  metric: string;
+  from: number;
+  to: number;
+  unit: string;
+  description: string;
+}
+
+export interface SoakReport {
+  intervals: SoakInterval[];
+  trends: SoakTrend[];
+  snapshot_file: string | null;
+}
+
+// Latency including the waits hidden by coordinated omission
+export interface CorrectedLatency {
+  max_response_time: number;
+  p50_response_time: number;
+  p90_response_time: number;
+  p95_response_time: number;
+  p99_response_time: number;
+}
+
+export interface ThresholdOutcome {
+  expression: string;
+  // null when there was no data to measure
+  observed: number | null;
+  limit: number;
+  unit: string;
+  passed: boolean;
+}
+
+// Set on results whose config had thresholds
+export interface Verdict {
+  passed: boolean;
+  thresholds: ThresholdOutcome[];
+}
+
+export interface AddressStats {
+  total_requests: number;
+  successful_requests: number;
+  failed_requests: number;
+  errors: number;
+  min_response_time: number;
+  max_response_time: number;
+  avg_response_time: number;
+  total_response_time: number;
+  status_codes: Record<string, number>;
 }
 
 export interface AggregatedResult extends TestResult {
-  clientCount: number;
+  client_count: number;
+  client_clocks?: Record<string, { offset: number; uncertainty: number | null }>;
+  // Workers that skipped or failed the run, with their reason
+  declined?: Record<string, string>;
+}
+
+export interface WorkerInfo {
+  hostname: string;
+  os: string;
+  cpu_cores: number;
+  available_memory: number;
+  label: string | null;
+  app_version: string;
+  max_concurrency: number | null;
+}
+
+export interface WorkerProfile {
+  label: string | null;
+  max_concurrency: number | null;
+}
+
+export interface RoomMember {
+  client_id: string;
+  capacity: number;
+  worker: WorkerInfo | null;
+}
+
+export type RoomEvent =
+  | { type: "connected"; payload: { client_id: string } }
+  | {
+      type: "client-joined";
+      payload: { client_id: string; client_count: number; member: RoomMember | null };
+    }
+  | { type: "client-left"; payload: { client_id: string; client_count: number } }
+  | { type: "host-changed"; payload: { new_host: string } }
+  | { type: "room-assigned"; payload: { room_id: string } }
+  | { type: "test-configured"; payload: TestConfig }
+  | { type: "test-started"; payload: { start_time: number; config: TestConfig } }
+  | { type: "test-aborted"; payload: { client_id: string; reason: string } }
+  | { type: "test-completed"; payload: AggregatedResult }
+  | { type: "live-metrics"; payload: LiveMetrics }
+  | { type: "disconnected" }
+  | { type: "reconnected"; payload: { room_id: string | null } };
+
+export interface LiveMetrics {
+  worker_count: number;
+  total_requests: number;
+  successful_requests: number;
+  failed_requests: number;
+  total_response_time: number;
+  status_codes: Record<string, number>;
+  throughput: number;
+  workers: Record<string, number>;
+  p50_response_time: number;
+  p95_response_time: number;
+  p99_response_time: number;
+}
+
+export type WorkerEvent =
+  | { type: "running"; payload: TestConfig }
+  | { type: "finished"; payload: TestResult }
+  | { type: "failed"; payload: string };
+
+export interface ConnectionHealth {
+  connected: boolean;
+  last_seen: number | null;
+  rtt_ms: number | null;
+  missed_pongs: number;
 }
 
+let unlistenRoomEvents: UnlistenFn | null = null;
+let unlistenHealth: UnlistenFn | null = null;
+let unlistenWorkerEvents: UnlistenFn | null = null;
+
 interface RoomState {
   roomId: string | null;
   clientCount: number;
   status: "waiting" | "configured" | "running" | "completed";
   isHost: boolean;
+  members: RoomMember[];
 }
 
 interface StoreState {
@@ -42,6 +288,14 @@ interface StoreState {
   serverUrl: string;
   isConnected: boolean;
   connectionError: string | null;
+  clientId: string | null;
+  connectionHealth: ConnectionHealth | null;
+
+  // Whether room tests run automatically when the host starts them
+  autoRun: boolean;
+  // Relative share of a room-wide load target this client takes
+  capacity: number;
+  workerError: string | null;
 
   // Room state
   room: RoomState;
@@ -52,14 +306,23 @@ interface StoreState {
   // Test results
   localResult: TestResult | null;
   aggregatedResult: AggregatedResult | null;
+  liveMetrics: LiveMetrics | null;
 
   // Actions
   setServerUrl: (url: string) => void;
+  handleRoomEvent: (event: RoomEvent) => void;
+  handleWorkerEvent: (event: WorkerEvent) => void;
+  setAutoRun: (enabled: boolean) => Promise<boolean>;
+  setCapacity: (capacity: number) => Promise<boolean>;
+  setWorkerProfile: (profile: WorkerProfile) => Promise<boolean>;
+  startCoordinator: (listenAddr?: string) => Promise<boolean>;
+  stopCoordinator: () => Promise<boolean>;
   connectToServer: () => Promise<boolean>;
   createRoom: () => Promise<boolean>;
   joinRoom: (roomId: string) => Promise<boolean>;
   configureTest: (config: TestConfig) => Promise<boolean>;
   startTest: () => Promise<boolean>;
+  assignWorkers: (count?: number) => Promise<string[] | null>;
   runTest: (config: TestConfig) => Promise<TestResult | null>;
   submitResults: (results: TestResult) => Promise<boolean>;
   leaveRoom: () => Promise<boolean>;
@@ -81,6 +344,12 @@ const useStore = create<StoreState>((set, get) => ({
   serverUrl: "wss://armandra.koompi.cloud",
   isConnected: false,
   connectionError: null,
+  clientId: null,
+  connectionHealth: null,
+
+  autoRun: true,
+  capacity: 1,
+  workerError: null,
 
   // Room state
   room: {
@@ -88,6 +357,7 @@ const useStore = create<StoreState>((set, get) => ({
     clientCount: 0,
     status: "waiting",
     isHost: false,
+    members: [],
   },
 
   // Test configuration
@@ -96,12 +366,181 @@ const useStore = create<StoreState>((set, get) => ({
   // Test results
   localResult: null,
   aggregatedResult: null,
+  liveMetrics: null,
 
   // Actions
   setServerUrl: (url: string) => set({ serverUrl: url }),
 
+  handleRoomEvent: (event: RoomEvent) => {
+    const room = get().room;
+
+    switch (event.type) {
+      case "connected":
+        set({ clientId: event.payload.client_id });
+        break;
+      case "client-joined": {
+        const member = event.payload.member;
+        set({
+          room: {
+            ...room,
+            clientCount: event.payload.client_count,
+            members: member
+              ? [...room.members.filter((m) => m.client_id !== member.client_id), member]
+              : room.members,
+          },
+        });
+        break;
+      }
+      case "client-left":
+        set({
+          room: {
+            ...room,
+            clientCount: event.payload.client_count,
+            members: room.members.filter((m) => m.client_id !== event.payload.client_id),
+          },
+        });
+        break;
+      case "host-changed":
+        set({
+          room: { ...room, isHost: event.payload.new_host === get().clientId },
+        });
+        break;
+      case "test-configured":
+        set({
+          testConfig: event.payload,
+          room: { ...room, status: "configured" },
+        });
+        break;
+      case "test-started":
+        set({
+          liveMetrics: null,
+          testConfig: event.payload.config,
+          room: { ...room, status: "running" },
+        });
+        break;
+      case "test-aborted":
+        set({ workerError: `Test aborted: ${event.payload.reason}` });
+        break;
+      case "test-completed":
+        set({
+          aggregatedResult: event.payload,
+          room: { ...room, status: "completed" },
+        });
+        break;
+      case "live-metrics":
+        set({ liveMetrics: event.payload });
+        break;
+      case "disconnected":
+        set({ isConnected: false, connectionError: "Connection lost, reconnecting..." });
+        break;
+      case "reconnected":
+        set({ isConnected: true, connectionError: null });
+        break;
+    }
+  },
+
+  handleWorkerEvent: (event: WorkerEvent) => {
+    switch (event.type) {
+      case "running":
+        set({ workerError: null });
+        break;
+      case "finished":
+        set({ localResult: event.payload });
+        break;
+      case "failed":
+        set({ workerError: event.payload });
+        break;
+    }
+  },
+
+  setAutoRun: async (enabled: boolean) => {
+    try {
+      const response: any = await invoke("set_auto_run", { enabled });
+
+      if (response.success) {
+        set({ autoRun: enabled });
+        return true;
+      } else {
+        return false;
+      }
+    } catch (error) {
+      console.error("Error setting auto run:", error);
+      return false;
+    }
+  },
+
+  setCapacity: async (capacity: number) => {
+    try {
+      const response: any = await invoke("set_capacity", { capacity });
+
+      if (response.success) {
+        set({ capacity });
+        return true;
+      } else {
+        return false;
+      }
+    } catch (error) {
+      console.error("Error setting capacity:", error);
+      return false;
+    }
+  },
+
+  setWorkerProfile: async (profile: WorkerProfile) => {
+    try {
+      const response: any = await invoke("set_worker_profile", { profile });
+      return response.success;
+    } catch (error) {
+      console.error("Error setting worker profile:", error);
+      return false;
+    }
+  },
+
+  // Runs a coordinator inside the app and points the connection at it
+  startCoordinator: async (listenAddr?: string) => {
+    try {
+      const response: any = await invoke("start_coordinator", { listenAddr });
+
+      if (response.success) {
+        set({ serverUrl: response.url });
+        return true;
+      } else {
+        console.error("Error starting coordinator:", response.error);
+        return false;
+      }
+    } catch (error) {
+      console.error("Error starting coordinator:", error);
+      return false;
+    }
+  },
+
+  stopCoordinator: async () => {
+    try {
+      const response: any = await invoke("stop_coordinator", {});
+      return response.success;
+    } catch (error) {
+      console.error("Error stopping coordinator:", error);
+      return false;
+    }
+  },
+
   connectToServer: async () => {
     try {
+      if (!unlistenHealth) {
+        unlistenHealth = await listen<ConnectionHealth>("connection-health", (event) =>
+          set({ connectionHealth: event.payload })
+        );
+      }
+      if (!unlistenWorkerEvents) {
+        unlistenWorkerEvents = await listen<WorkerEvent>("worker-event", (event) =>
+          get().handleWorkerEvent(event.payload)
+        );
+      }
+      if (!unlistenRoomEvents) {
+        unlistenRoomEvents = await listen<RoomEvent>("room-event", (event) =>
+          get().handleRoomEvent(event.payload)
+        );
+      }
+
       const response: any = await invoke("connect_to_server", {
         serverUrl: get().serverUrl,
       });
@@ -136,6 +575,7 @@ const useStore = create<StoreState>((set, get) => ({
             clientCount: response.client_count || 1,
             status: "waiting",
             isHost: true,
+            members: response.members || [],
           },
         });
         return true;
@@ -166,6 +606,7 @@ const useStore = create<StoreState>((set, get) => ({
             clientCount: response.client_count || 1,
             status: (response.status as any) || "waiting",
             isHost: false,
+            members: response.members || [],
           },
           testConfig: response.config || { ...defaultTestConfig },
         });
@@ -223,6 +664,21 @@ const useStore = create<StoreState>((set, get) => ({
     }
   },
 
+  assignWorkers: async (count?: number) => {
+    try {
+      const response: any = await invoke("assign_workers", { count });
+
+      if (response.success) {
+        return response.assigned || [];
+      } else {
+        return null;
+      }
+    } catch (error) {
+      console.error("Error assigning workers:", error);
+      return null;
+    }
+  },
+
   runTest: async (config: TestConfig) => {
     try {
       const response: any = await invoke("run_test", { config });
@@ -260,6 +716,7 @@ const useStore = create<StoreState>((set, get) => ({
             clientCount: 0,
             status: "waiting",
             isHost: false,
+            members: [],
           },
           testConfig: { ...defaultTestConfig },
           localResult: null,
@@ -282,6 +739,7 @@ const useStore = create<StoreState>((set, get) => ({
         clientCount: 0,
         status: "waiting",
         isHost: false,
+        members: [],
       },
       testConfig: { ...defaultTestConfig },
       localResult: null,